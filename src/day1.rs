@@ -1,41 +1,146 @@
-fn parse_input(input: &str) -> (Vec<i32>, Vec<i32>) {
-    let size = input.lines().count();
-    let mut lhs = Vec::with_capacity(size);
-    let mut rhs = Vec::with_capacity(size);
-    for l in input.lines() {
-        if let Some((left, right)) = l.split_once(|c: char| c.is_whitespace()) {
-            lhs.push(left.trim().parse::<i32>().expect("Not an i32"));
-            rhs.push(right.trim().parse::<i32>().expect("Not an i32"));
-        }
-    }
-    (lhs, rhs)
+use std::collections::BTreeMap;
+
+use crate::parse::{self, ParseError};
+
+fn parse_input(input: &str) -> Result<(Vec<i32>, Vec<i32>), ParseError> {
+    let mut columns = parse::columns::<i32>(input, 2)?;
+    let rhs = columns.pop().unwrap_or_default();
+    let lhs = columns.pop().unwrap_or_default();
+    Ok((lhs, rhs))
 }
 
-fn part1(lhs: &mut [i32], rhs: &mut [i32]) -> i32 {
-    lhs.sort();
-    rhs.sort();
+/// The n:th smallest ids of both lists
+#[derive(Debug, PartialEq, Eq)]
+struct Pair {
+    left: i32,
+    right: i32,
+    distance: i32,
+}
 
-    lhs.iter().zip(rhs).fold(0_i32, |acc, (left, right)| {
-        acc + (left.abs_diff(*right) as i32)
-    })
+/// How much one id in the left list adds to the similarity score
+#[derive(Debug, PartialEq, Eq)]
+struct Contribution {
+    id: i32,
+    /// Times the id is in the left list
+    left_count: usize,
+    /// Times the id is in the right list
+    right_count: usize,
+    score: i32,
 }
 
-fn part2(lhs: &mut [i32], rhs: &mut [i32]) -> i32 {
+fn pairs(lhs: &[i32], rhs: &[i32]) -> Vec<Pair> {
+    let mut lhs = lhs.to_vec();
+    let mut rhs = rhs.to_vec();
     lhs.sort();
     rhs.sort();
 
-    let mut acc = 0;
-    for x in lhs.iter() {
-        acc += x * rhs.iter().filter(|&y| *y == *x).count() as i32;
+    lhs.into_iter()
+        .zip(rhs)
+        .map(|(left, right)| Pair {
+            left,
+            right,
+            distance: left.abs_diff(right) as i32,
+        })
+        .collect()
+}
+
+fn frequencies(ids: &[i32]) -> BTreeMap<i32, usize> {
+    let mut counts = BTreeMap::new();
+    for id in ids {
+        *counts.entry(*id).or_insert(0) += 1;
     }
-    acc
+    counts
+}
+
+/// One entry per distinct id in the left list, in id order
+fn contributions(lhs: &[i32], rhs: &[i32]) -> Vec<Contribution> {
+    let right = frequencies(rhs);
+    frequencies(lhs)
+        .into_iter()
+        .map(|(id, left_count)| {
+            let right_count = right.get(&id).copied().unwrap_or(0);
+            Contribution {
+                id,
+                left_count,
+                right_count,
+                score: id * (left_count * right_count) as i32,
+            }
+        })
+        .collect()
+}
+
+fn part1(lhs: &[i32], rhs: &[i32]) -> i32 {
+    pairs(lhs, rhs).iter().map(|p| p.distance).sum()
+}
+
+fn part2(lhs: &[i32], rhs: &[i32]) -> i32 {
+    contributions(lhs, rhs).iter().map(|c| c.score).sum()
+}
+
+/// Lists every pair, a histogram of the right list and the `top` ids adding the most to each
+/// score
+fn report(lhs: &[i32], rhs: &[i32], top: usize) -> String {
+    const BAR_WIDTH: usize = 40;
+    let mut output = Vec::new();
+
+    let mut pairs = pairs(lhs, rhs);
+    output.push("Pairs (left right distance):".to_string());
+    for p in pairs.iter() {
+        output.push(format!("{:>8} {:>8} {:>8}", p.left, p.right, p.distance));
+    }
+
+    let right = frequencies(rhs);
+    let max_count = right.values().copied().max().unwrap_or(1);
+    output.push(String::new());
+    output.push("Right list histogram (id count):".to_string());
+    for (id, count) in right.iter() {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max_count));
+        output.push(format!("{id:>8} {count:>4} {bar}"));
+    }
+
+    // Stable sorts keep the smallest ids first on ties
+    pairs.sort_by_key(|p| std::cmp::Reverse(p.distance));
+    output.push(String::new());
+    output.push(format!("Largest distances, total {}:", part1(lhs, rhs)));
+    for p in pairs.iter().take(top) {
+        output.push(format!("{:>8} {:>8} {:>8}", p.left, p.right, p.distance));
+    }
+
+    let mut contributions = contributions(lhs, rhs);
+    contributions.sort_by_key(|c| std::cmp::Reverse(c.score));
+    output.push(String::new());
+    output.push(format!(
+        "Largest similarity scores, total {}:",
+        part2(lhs, rhs)
+    ));
+    for c in contributions.iter().take(top) {
+        output.push(format!(
+            "{:>8} x{} left x{} right = {}",
+            c.id, c.left_count, c.right_count, c.score
+        ));
+    }
+
+    output.join("\n")
 }
 
 pub fn main() {
     let input = std::fs::read_to_string("input/day1.txt").expect("No input");
-    let (mut lhs, mut rhs) = parse_input(&input);
-    println!("Part1: {}", part1(&mut lhs, &mut rhs));
-    println!("Part2: {}", part2(&mut lhs, &mut rhs));
+    let (lhs, rhs) = match parse_input(&input) {
+        Ok(lists) => lists,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("report") {
+        // `cargo run 1 report 5` shows the five largest contributors to each score
+        let top = args.get(3).and_then(|s| s.parse().ok()).unwrap_or(10);
+        println!("{}", report(&lhs, &rhs, top));
+        return;
+    }
+    println!("Part1: {}", part1(&lhs, &rhs));
+    println!("Part2: {}", part2(&lhs, &rhs));
 }
 
 #[cfg(test)]
@@ -50,13 +155,59 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        let (mut left, mut right) = parse_input(INPUT);
-        assert_eq!(part1(&mut left, &mut right), 11);
+        let (left, right) = parse_input(INPUT).unwrap();
+        assert_eq!(part1(&left, &right), 11);
+        // The lists are left as they were
+        assert_eq!(left, vec![3, 4, 2, 1, 3, 3]);
     }
 
     #[test]
     fn test_part2() {
-        let (mut left, mut right) = parse_input(INPUT);
-        assert_eq!(part2(&mut left, &mut right), 31);
+        let (left, right) = parse_input(INPUT).unwrap();
+        assert_eq!(part2(&left, &right), 31);
+    }
+
+    #[test]
+    fn test_contributions() {
+        let (left, right) = parse_input(INPUT).unwrap();
+        let contributions = contributions(&left, &right);
+        assert_eq!(contributions.len(), 4);
+        assert_eq!(
+            contributions[2],
+            Contribution {
+                id: 3,
+                left_count: 3,
+                right_count: 3,
+                score: 27
+            }
+        );
+        assert_eq!(contributions[0].score, 0);
+    }
+
+    #[test]
+    fn test_report() {
+        let (left, right) = parse_input(INPUT).unwrap();
+        let report = report(&left, &right, 1);
+        let expected = "\
+Pairs (left right distance):
+       1        3        2
+       2        3        1
+       3        3        0
+       3        4        1
+       3        5        2
+       4        9        5
+
+Right list histogram (id count):
+       3    3 ########################################
+       4    1 ##############
+       5    1 ##############
+       9    1 ##############
+
+Largest distances, total 11:
+       4        9        5
+
+Largest similarity scores, total 31:
+       3 x3 left x3 right = 27";
+        assert_eq!(report, expected);
     }
 }
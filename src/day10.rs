@@ -1,8 +1,30 @@
+use std::collections::HashSet;
+
 use glam::IVec2;
-use itertools::Itertools;
 
 pub fn main() {
     let input = std::fs::read_to_string("input/day10.txt").expect("No input");
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("trails") {
+        // `cargo run 10 trails 4,0` lists all trails from the trailhead at x = 4, y = 0,
+        // `cargo run 10 trails 4,0 2` only shows the third one
+        let map = parse_map(&input);
+        let pos = args
+            .get(3)
+            .and_then(|s| s.split_once(','))
+            .and_then(|(x, y)| Some(IVec2::new(x.parse().ok()?, y.parse().ok()?)))
+            .expect("Expected a trailhead as x,y");
+        let trailhead = pos_to_idx(pos, map.rows, map.cols).expect("Out of bounds");
+        let trails = list_trails(&map, trailhead);
+        println!("Trails from {pos}: {}", trails.len());
+        let selected = args.get(4).and_then(|s| s.parse::<usize>().ok());
+        for (i, trail) in trails.iter().enumerate() {
+            if selected.is_none_or(|s| s == i) {
+                println!("Trail {i}:\n{}\n", render_trail(&map, trail));
+            }
+        }
+        return;
+    }
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
 }
@@ -11,113 +33,154 @@ fn pos_to_idx(pos: IVec2, rows: usize, cols: usize) -> Option<usize> {
     if pos.x >= cols as i32 || pos.y >= rows as i32 || pos.x < 0 || pos.y < 0 {
         return None;
     }
-    Some((pos.y * (rows as i32) + pos.x) as usize)
+    Some((pos.y * (cols as i32) + pos.x) as usize)
 }
 
 fn idx_to_pos(i: usize, rows: usize, cols: usize) -> Option<IVec2> {
     if i >= rows * cols {
         return None;
     }
-    let x = i % rows;
-    let y = i / rows;
+    let x = i % cols;
+    let y = i / cols;
     Some(IVec2 {
         x: x as i32,
         y: y as i32,
     })
 }
 
-fn get_rows_cols(input: &str) -> (usize, usize) {
-    let rows = input
+#[derive(Debug)]
+struct TrailMap {
+    rows: usize,
+    cols: usize,
+    /// `None` for impassable tiles
+    heights: Vec<Option<u8>>,
+}
+impl TrailMap {
+    fn neighbors(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        let pos = idx_to_pos(idx, self.rows, self.cols).unwrap();
+        [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y]
+            .into_iter()
+            .filter_map(move |n| pos_to_idx(pos + n, self.rows, self.cols))
+    }
+
+    /// Neighbors that are exactly one step higher
+    fn uphill(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        let next_height = self.heights[idx].map(|h| h + 1);
+        self.neighbors(idx)
+            .filter(move |&n| next_height.is_some() && self.heights[n] == next_height)
+    }
+
+    fn trailheads(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.heights.len()).filter(|&i| self.heights[i] == Some(0))
+    }
+}
+
+fn parse_map(input: &str) -> TrailMap {
+    let cols = input
         .lines()
-        .nth(0)
+        .next()
         .expect("Received empty input")
         .chars()
         .count();
-    let cols = input.lines().count();
-    (rows, cols)
+    let rows = input.lines().count();
+    let heights = input
+        .lines()
+        .flat_map(|l| l.chars())
+        .map(|c| c.to_digit(10).map(|h| h as u8))
+        .collect();
+    TrailMap {
+        rows,
+        cols,
+        heights,
+    }
 }
 
-fn find_path_endpoints(
-    height: i32,
-    idx: usize,
-    rows: usize,
-    cols: usize,
-    trail_map: &str,
-) -> Vec<IVec2> {
-    let next_height = height + 1;
-    let Some(pos) = idx_to_pos(idx, rows, cols) else {
-        return vec![];
-    };
-    if trail_map.chars().nth(idx) == Some('9') {
-        return vec![pos];
-    }
-    let neighbors = [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y];
+/// Reachable peaks and number of distinct trails for every tile
+struct Trails {
+    peaks: Vec<HashSet<usize>>,
+    ratings: Vec<usize>,
+}
 
-    let mut output = Vec::new();
-    for n in neighbors.iter().map(|p| p + pos) {
-        if let Some(i) = pos_to_idx(n, rows, cols) {
-            if trail_map.chars().nth(i).and_then(|c| c.to_digit(10)) == Some(next_height as u32) {
-                output.append(&mut find_path_endpoints(
-                    next_height,
-                    i,
-                    rows,
-                    cols,
-                    trail_map,
-                ));
+/// Computes the trails bottom up, from the peaks at height 9 down to the trailheads, so every
+/// tile only has to combine the results of its uphill neighbors.
+fn find_trails(map: &TrailMap) -> Trails {
+    let mut peaks = vec![HashSet::new(); map.heights.len()];
+    let mut ratings = vec![0; map.heights.len()];
+
+    for height in (0..=9).rev() {
+        for i in (0..map.heights.len()).filter(|&i| map.heights[i] == Some(height)) {
+            if height == 9 {
+                peaks[i].insert(i);
+                ratings[i] = 1;
+                continue;
+            }
+            for n in map.uphill(i) {
+                let reached = peaks[n].clone();
+                peaks[i].extend(reached);
+                ratings[i] += ratings[n];
             }
         }
     }
-    output
+    Trails { peaks, ratings }
 }
 
-fn part1(input: &str) -> i32 {
-    let (rows, cols) = get_rows_cols(input);
-    let input = input.lines().collect::<String>();
-
-    let mut acc = 0;
-    for (i, c) in input.chars().enumerate() {
-        if c == '0' {
-            let path = find_path_endpoints(0, i, rows, cols, &input);
-            acc += path.iter().unique().count() as i32;
-        }
+/// Every distinct hiking trail from `trailhead` to a peak, as positions from height 0 to 9
+fn list_trails(map: &TrailMap, trailhead: usize) -> Vec<Vec<IVec2>> {
+    let trails = find_trails(map);
+    let mut output = Vec::new();
+    if map.heights[trailhead] == Some(0) {
+        let mut trail = Vec::with_capacity(10);
+        walk_trails(map, &trails, trailhead, &mut trail, &mut output);
     }
-
-    acc
+    output
 }
 
-fn find_paths(height: i32, idx: usize, rows: usize, cols: usize, trail_map: &str) -> i32 {
-    let next_height = height + 1;
-    let Some(pos) = idx_to_pos(idx, rows, cols) else {
-        return 0;
-    };
-    if trail_map.chars().nth(idx) == Some('9') {
-        return 1;
+fn walk_trails(
+    map: &TrailMap,
+    trails: &Trails,
+    idx: usize,
+    trail: &mut Vec<IVec2>,
+    output: &mut Vec<Vec<IVec2>>,
+) {
+    trail.push(idx_to_pos(idx, map.rows, map.cols).unwrap());
+    if map.heights[idx] == Some(9) {
+        output.push(trail.clone());
+    } else {
+        // Only follow tiles that lead to a peak
+        for n in map.uphill(idx).filter(|&n| trails.ratings[n] > 0) {
+            walk_trails(map, trails, n, trail, output);
+        }
     }
-    let neighbors = [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y];
+    trail.pop();
+}
 
-    let mut acc = 0;
-    for n in neighbors.iter().map(|p| p + pos) {
-        if let Some(i) = pos_to_idx(n, rows, cols) {
-            if trail_map.chars().nth(i).and_then(|c| c.to_digit(10)) == Some(next_height as u32) {
-                acc += find_paths(next_height, i, rows, cols, trail_map);
+/// Draws the heights along the trail and `.` everywhere else
+fn render_trail(map: &TrailMap, trail: &[IVec2]) -> String {
+    let mut buffer = vec!['.'; map.heights.len()];
+    for &pos in trail {
+        if let Some(idx) = pos_to_idx(pos, map.rows, map.cols) {
+            if let Some(h) = map.heights[idx] {
+                buffer[idx] = char::from_digit(h as u32, 10).unwrap();
             }
         }
     }
-    acc
+    buffer
+        .chunks(map.cols)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-fn part2(input: &str) -> i32 {
-    let (rows, cols) = get_rows_cols(input);
-    let input = input.lines().collect::<String>();
-
-    let mut acc = 0;
-    for (i, c) in input.chars().enumerate() {
-        if c == '0' {
-            acc += find_paths(0, i, rows, cols, &input);
-        }
-    }
+fn part1(input: &str) -> i32 {
+    let map = parse_map(input);
+    let trails = find_trails(&map);
+    map.trailheads().map(|i| trails.peaks[i].len() as i32).sum()
+}
 
-    acc
+fn part2(input: &str) -> i32 {
+    let map = parse_map(input);
+    let trails = find_trails(&map);
+    map.trailheads().map(|i| trails.ratings[i] as i32).sum()
 }
 
 #[cfg(test)]
@@ -165,6 +228,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_map() {
+        let map = parse_map("0.\n19");
+        assert_eq!(map.heights, vec![Some(0), None, Some(1), Some(9)]);
+        assert_eq!(map.uphill(0).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(map.uphill(2).count(), 0);
+    }
+
+    #[test]
+    fn test_list_trails() {
+        let map = parse_map(
+            ".....0.
+..4321.
+..5..2.
+..6543.
+..7..4.
+..8765.
+..9....",
+        );
+        let trails = list_trails(&map, 5);
+        assert_eq!(trails.len(), 3);
+        assert_eq!(trails[0].first(), Some(&IVec2::new(5, 0)));
+        assert_eq!(trails[0].last(), Some(&IVec2::new(2, 6)));
+        assert_eq!(
+            render_trail(&map, &trails[0]),
+            ".....0.
+.....1.
+.....2.
+.....3.
+.....4.
+..8765.
+..9...."
+        );
+        assert!(list_trails(&map, 0).is_empty());
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(
@@ -179,5 +278,18 @@ mod tests {
 ..9...."
             )
         );
+        assert_eq!(
+            81,
+            part2(
+                "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732"
+            )
+        );
     }
 }
@@ -1,93 +1,266 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::parse::{self, ParseError};
 
 pub fn main() {
     let input = std::fs::read_to_string("input/day11.txt").expect("No input");
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    let stones = match parse_stones(&input) {
+        Ok(stones) => stones,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("stats") {
+        // `cargo run 11 stats 75 csv`
+        let blinks = args.get(3).and_then(|s| s.parse().ok()).unwrap_or(75);
+        let csv = args.get(4).map(String::as_str) == Some("csv");
+        match blink_stats(&stones, blinks, &plutonian) {
+            Ok(stats) => println!("{}", format_stats(&stats, csv)),
+            Err(e) => println!("{e}"),
+        }
+        return;
+    }
+    if let Some(blinks) = std::env::args()
+        .nth(2)
+        .and_then(|s| s.parse::<usize>().ok())
+    {
+        let mut engine = Engine::new(plutonian);
+        match engine.count_all(&stones, blinks) {
+            Ok(count) => println!("Stones after {blinks} blinks: {count}"),
+            Err(e) => println!("Stones after {blinks} blinks: {e}"),
+        }
+        return;
+    }
+    match part1(&stones) {
+        Ok(count) => println!("Part 1: {count}"),
+        Err(e) => println!("Part 1: {e}"),
+    }
+    match part2(&stones) {
+        Ok(count) => println!("Part 2: {count}"),
+        Err(e) => println!("Part 2: {e}"),
+    }
 }
 
-type Stone = i64;
+type Stone = u64;
 type Stones = Vec<Stone>;
+type Count = u128;
 
-fn parse_stones(input: &str) -> Stones {
-    // let mut list = List::new();
-    let mut vec = Vec::new();
-    for c in input
-        .split_whitespace()
-        .filter_map(|c| c.parse::<Stone>().ok())
-    {
-        vec.push(c);
+#[derive(Debug, PartialEq, Eq)]
+enum BlinkError {
+    /// The rule could not transform this stone without overflowing
+    StoneOverflow(Stone),
+    CountOverflow,
+}
+impl std::fmt::Display for BlinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlinkError::StoneOverflow(s) => write!(f, "stone {s} overflows when transformed"),
+            BlinkError::CountOverflow => write!(f, "stone count overflows"),
+        }
     }
-    vec
 }
 
-fn blink(stones: Stones) -> Stones {
+/// Transforms a single stone into the stones it becomes after a blink. Returns `None` if a new
+/// stone does not fit in a `Stone`.
+trait Rule {
+    fn apply(&self, stone: Stone) -> Option<Stones>;
+}
+impl<F: Fn(Stone) -> Option<Stones>> Rule for F {
+    fn apply(&self, stone: Stone) -> Option<Stones> {
+        self(stone)
+    }
+}
+
+/// The rules from the puzzle
+fn plutonian(s: Stone) -> Option<Stones> {
+    if s == 0 {
+        Some(vec![1])
+    } else if s.ilog10() % 2 == 1 {
+        let num_digits = s.ilog10() + 1;
+        let half = 10_u64.pow(num_digits / 2);
+        Some(vec![s / half, s % half])
+    } else {
+        Some(vec![s.checked_mul(2024)?])
+    }
+}
+
+fn transform(rule: &impl Rule, stone: Stone) -> Result<Stones, BlinkError> {
+    rule.apply(stone).ok_or(BlinkError::StoneOverflow(stone))
+}
+
+/// Counts stones with a `(stone, blinks) -> count` cache that is kept between queries
+struct Engine<R: Rule> {
+    rule: R,
+    cache: HashMap<(Stone, usize), Count>,
+}
+impl<R: Rule> Engine<R> {
+    pub fn new(rule: R) -> Self {
+        Self {
+            rule,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn cached(&self, stone: Stone, blinks: usize) -> Option<Count> {
+        match blinks {
+            0 => Some(1),
+            _ => self.cache.get(&(stone, blinks)).copied(),
+        }
+    }
+
+    /// Walks the stones with an explicit stack, as recursing once per blink overflows the call
+    /// stack for large blink counts
+    pub fn count(&mut self, stone: Stone, blinks: usize) -> Result<Count, BlinkError> {
+        let mut stack = vec![(stone, blinks)];
+        while let Some(&(s, b)) = stack.last() {
+            if self.cached(s, b).is_some() {
+                stack.pop();
+                continue;
+            }
+            let next = transform(&self.rule, s)?;
+            let pending = next
+                .iter()
+                .filter(|&&n| self.cached(n, b - 1).is_none())
+                .map(|&n| (n, b - 1))
+                .collect::<Vec<_>>();
+            if !pending.is_empty() {
+                stack.extend(pending);
+                continue;
+            }
+            let mut count: Count = 0;
+            for n in next {
+                count = count
+                    .checked_add(self.cached(n, b - 1).unwrap())
+                    .ok_or(BlinkError::CountOverflow)?;
+            }
+            self.cache.insert((s, b), count);
+            stack.pop();
+        }
+        Ok(self.cached(stone, blinks).unwrap())
+    }
+
+    pub fn count_all(&mut self, stones: &[Stone], blinks: usize) -> Result<Count, BlinkError> {
+        stones.iter().try_fold(0 as Count, |acc, &s| {
+            acc.checked_add(self.count(s, blinks)?)
+                .ok_or(BlinkError::CountOverflow)
+        })
+    }
+}
+
+fn parse_stones(input: &str) -> Result<Stones, ParseError> {
+    let mut stones = Vec::new();
+    for (i, l) in input.lines().enumerate() {
+        stones.extend(parse::fields::<Stone>(l, i + 1)?);
+    }
+    Ok(stones)
+}
+
+fn blink(stones: Stones, rule: &impl Rule) -> Result<Stones, BlinkError> {
     let mut output = Vec::new();
     for s in stones.into_iter() {
-        if s == 0 {
-            output.push(1);
-        } else if s.ilog10() % 2 == 1 {
-            let num_digits = s.ilog10() + 1;
-            let half = num_digits / 2;
-            let first = s.div_euclid(10_i64.pow(half));
-            let second = s.rem_euclid(10_i64.pow(half));
-            output.push(first);
-            output.push(second);
-        } else {
-            output.push(s * 2024);
-        }
+        output.append(&mut transform(rule, s)?);
     }
-    output
+    Ok(output)
 }
 
-fn part1(input: &str) -> i32 {
-    let mut stones = parse_stones(input);
+fn part1(stones: &[Stone]) -> Result<usize, BlinkError> {
+    let mut stones = stones.to_vec();
     for _ in 0..25 {
-        stones = blink(stones);
+        stones = blink(stones, &plutonian)?;
     }
-    stones.len() as i32
+    Ok(stones.len())
 }
 
-fn blink_with_map(stones: BTreeMap<i64, i64>) -> BTreeMap<i64, i64> {
+fn blink_with_map(
+    stones: BTreeMap<Stone, Count>,
+    rule: &impl Rule,
+) -> Result<BTreeMap<Stone, Count>, BlinkError> {
     let mut output = BTreeMap::new();
     for (s, num) in stones.into_iter() {
-        if s == 0 {
-            insert(&mut output, 1, num);
-        } else if s.ilog10() % 2 == 1 {
-            let num_digits = s.ilog10() + 1;
-            let half = num_digits / 2;
-            let first = s.div_euclid(10_i64.pow(half));
-            let second = s.rem_euclid(10_i64.pow(half));
-            insert(&mut output, first, num);
-            insert(&mut output, second, num);
-        } else {
-            insert(&mut output, s * 2024, num);
+        for new in transform(rule, s)? {
+            insert(&mut output, new, num)?;
         }
     }
-    output
+    Ok(output)
 }
 
-fn insert(m: &mut BTreeMap<i64, i64>, key: i64, val: i64) {
-    if let Some(v) = m.get_mut(&key) {
-        *v += val;
-    } else {
-        m.insert(key, val);
-    }
+fn insert(m: &mut BTreeMap<Stone, Count>, key: Stone, val: Count) -> Result<(), BlinkError> {
+    let v = m.entry(key).or_insert(0);
+    *v = v.checked_add(val).ok_or(BlinkError::CountOverflow)?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+struct BlinkStats {
+    blink: usize,
+    total: Count,
+    distinct: usize,
+    largest: Stone,
+    /// Total compared to the blink before
+    growth: f64,
 }
 
-fn part2(input: &str) -> i64 {
-    let stones = parse_stones(input);
-    let mut stones_map: BTreeMap<i64, i64> = BTreeMap::new();
-    for s in stones {
-        insert(&mut stones_map, s, 1);
+fn blink_stats(
+    stones: &[Stone],
+    blinks: usize,
+    rule: &impl Rule,
+) -> Result<Vec<BlinkStats>, BlinkError> {
+    let mut stones_map = BTreeMap::new();
+    for &s in stones {
+        insert(&mut stones_map, s, 1)?;
+    }
+
+    let mut previous = stones.len() as Count;
+    let mut stats = Vec::with_capacity(blinks);
+    for blink in 1..=blinks {
+        stones_map = blink_with_map(stones_map, rule)?;
+        let total = stones_map
+            .values()
+            .try_fold(0 as Count, |acc, &v| acc.checked_add(v))
+            .ok_or(BlinkError::CountOverflow)?;
+        stats.push(BlinkStats {
+            blink,
+            total,
+            distinct: stones_map.len(),
+            // The map is sorted, so the last key is the largest
+            largest: stones_map.keys().next_back().copied().unwrap_or(0),
+            growth: total as f64 / previous as f64,
+        });
+        previous = total;
     }
-    println!("{:?}", stones_map);
+    Ok(stats)
+}
 
-    for _ in 0..75 {
-        stones_map = blink_with_map(stones_map);
+fn format_stats(stats: &[BlinkStats], csv: bool) -> String {
+    let mut out = if csv {
+        String::from("blink,total,distinct,largest,growth\n")
+    } else {
+        format!(
+            "{:>5} {:>20} {:>8} {:>20} {:>7}\n",
+            "blink", "total", "distinct", "largest", "growth"
+        )
+    };
+    for s in stats {
+        out += &if csv {
+            format!(
+                "{},{},{},{},{:.3}\n",
+                s.blink, s.total, s.distinct, s.largest, s.growth
+            )
+        } else {
+            format!(
+                "{:>5} {:>20} {:>8} {:>20} {:>7.3}\n",
+                s.blink, s.total, s.distinct, s.largest, s.growth
+            )
+        };
     }
+    out
+}
 
-    stones_map.into_iter().fold(0, |acc, (_, v)| acc + v)
+fn part2(stones: &[Stone]) -> Result<Count, BlinkError> {
+    let mut engine = Engine::new(plutonian);
+    engine.count_all(stones, 75)
 }
 
 #[cfg(test)]
@@ -105,29 +278,94 @@ mod tests {
 
     #[test]
     fn test_parse() {
-        assert_eq!(print_stones(parse_stones("125 17")), "125 17");
+        assert_eq!(print_stones(parse_stones("125 17").unwrap()), "125 17");
+        assert_eq!(
+            parse_stones("125 17\n-3").unwrap_err().to_string(),
+            "line 2: invalid value '-3'"
+        );
+    }
+
+    #[test]
+    fn test_engine() {
+        let stones = parse_stones("125 17").unwrap();
+        let mut engine = Engine::new(plutonian);
+        assert_eq!(engine.count_all(&stones, 6), Ok(22));
+        assert_eq!(engine.count_all(&stones, 25), Ok(55312));
+
+        let mut map = BTreeMap::new();
+        for s in stones.iter() {
+            insert(&mut map, *s, 1).unwrap();
+        }
+        for _ in 0..25 {
+            map = blink_with_map(map, &plutonian).unwrap();
+        }
+        assert_eq!(map.values().sum::<Count>(), 55312);
+    }
+
+    #[test]
+    fn test_blink_stats() {
+        let stats = blink_stats(&parse_stones("125 17").unwrap(), 3, &plutonian).unwrap();
+        assert_eq!(
+            stats[1],
+            BlinkStats {
+                blink: 2,
+                total: 4,
+                distinct: 4,
+                largest: 14168,
+                growth: 4.0 / 3.0
+            }
+        );
+        assert_eq!(
+            format_stats(&stats, true),
+            "blink,total,distinct,largest,growth
+1,3,3,253000,1.500
+2,4,4,14168,1.333
+3,5,5,28676032,1.250
+"
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut engine = Engine::new(plutonian);
+        assert_eq!(
+            engine.count(Stone::MAX / 1000, 1),
+            Err(BlinkError::StoneOverflow(Stone::MAX / 1000))
+        );
+        let doubling = |s: Stone| Some(vec![s, s]);
+        let mut engine = Engine::new(doubling);
+        assert_eq!(engine.count(1, 127), Ok(1 << 127));
+        assert_eq!(engine.count(1, 128), Err(BlinkError::CountOverflow));
+        assert_eq!(
+            part1(&[Stone::MAX / 1000]),
+            Err(BlinkError::StoneOverflow(Stone::MAX / 1000))
+        );
+        assert_eq!(part1(&[125, 17]), Ok(55312));
+        // Deep enough to overflow the stack if every blink was a function call
+        let mut engine = Engine::new(plutonian);
+        assert_eq!(engine.count(0, 100000), Err(BlinkError::CountOverflow));
     }
 
     #[test]
     fn test_blink() {
-        let mut stones = parse_stones("125 17");
-        stones = blink(stones);
+        let mut stones = parse_stones("125 17").unwrap();
+        stones = blink(stones, &plutonian).unwrap();
         assert_eq!(print_stones(stones.clone()), "253000 1 7");
-        stones = blink(stones);
+        stones = blink(stones, &plutonian).unwrap();
         assert_eq!(print_stones(stones.clone()), "253 0 2024 14168");
-        stones = blink(stones);
+        stones = blink(stones, &plutonian).unwrap();
         assert_eq!(print_stones(stones.clone()), "512072 1 20 24 28676032");
-        stones = blink(stones);
+        stones = blink(stones, &plutonian).unwrap();
         assert_eq!(
             print_stones(stones.clone()),
             "512 72 2024 2 0 2 4 2867 6032"
         );
-        stones = blink(stones);
+        stones = blink(stones, &plutonian).unwrap();
         assert_eq!(
             print_stones(stones.clone()),
             "1036288 7 2 20 24 4048 1 4048 8096 28 67 60 32"
         );
-        stones = blink(stones);
+        stones = blink(stones, &plutonian).unwrap();
         assert_eq!(
             print_stones(stones),
             "2097446912 14168 4048 2 0 2 4 40 48 2024 40 48 80 96 2 8 6 7 6 0 3 2"
@@ -1,32 +1,32 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 
 use glam::IVec2;
 
 pub fn main() {
     let input = std::fs::read_to_string("input/day12.txt").expect("No input");
+    if std::env::args().nth(2).as_deref() == Some("svg") {
+        std::fs::write("day12.svg", to_svg(&analyze(&input), 20))
+            .expect("Could not write day12.svg");
+        println!("Wrote day12.svg");
+        return;
+    }
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Tile {
-    crop: char,
-    perimeter: i32,
-}
-
 fn pos_to_idx(pos: IVec2, rows: usize, cols: usize) -> Option<usize> {
     if pos.x >= cols as i32 || pos.y >= rows as i32 || pos.x < 0 || pos.y < 0 {
         return None;
     }
-    Some((pos.y * (rows as i32) + pos.x) as usize)
+    Some((pos.y * (cols as i32) + pos.x) as usize)
 }
 
 fn idx_to_pos(i: usize, rows: usize, cols: usize) -> Option<IVec2> {
     if i >= rows * cols {
         return None;
     }
-    let x = i % rows;
-    let y = i / rows;
+    let x = i % cols;
+    let y = i / cols;
     Some(IVec2 {
         x: x as i32,
         y: y as i32,
@@ -34,83 +34,16 @@ fn idx_to_pos(i: usize, rows: usize, cols: usize) -> Option<IVec2> {
 }
 
 fn get_rows_cols(input: &str) -> (usize, usize) {
-    let rows = input
+    let cols = input
         .lines()
-        .nth(0)
+        .next()
         .expect("Received empty input")
         .chars()
         .count();
-    let cols = input.lines().count();
+    let rows = input.lines().count();
     (rows, cols)
 }
 
-fn part1(input: &str) -> i32 {
-    let (rows, cols) = get_rows_cols(input);
-    let tiles = parse_tiles(rows, cols, input);
-
-    let neighbors = [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y];
-    let mut visited = vec![false; tiles.len()];
-    let mut acc = 0;
-
-    for i in 0..tiles.len() {
-        if visited[i] {
-            continue;
-        }
-
-        let mut area = 0;
-        let mut perimeter = 0;
-        let crop = tiles[i].crop;
-        let mut to_visit = VecDeque::<usize>::new();
-        to_visit.push_back(i);
-
-        while let Some(j) = to_visit.pop_front() {
-            if visited[j] {
-                continue;
-            }
-            let this_pos = idx_to_pos(j, rows, cols).unwrap();
-            visited[j] = true;
-            area += 1;
-            perimeter += tiles[j].perimeter;
-            for n in neighbors
-                .iter()
-                .filter_map(|n| pos_to_idx(n + this_pos, rows, cols))
-                .filter(|k| tiles[*k].crop == crop)
-            {
-                to_visit.push_back(n);
-            }
-        }
-        acc += area * perimeter;
-    }
-
-    acc
-}
-
-fn parse_tiles(rows: usize, cols: usize, input: &str) -> Vec<Tile> {
-    let mut tiles: Vec<Tile> = Vec::with_capacity(rows * cols);
-
-    for c in input.lines().flat_map(|l| l.chars()) {
-        tiles.push(Tile {
-            crop: c,
-            perimeter: 0,
-        });
-    }
-
-    let neighbors = [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y];
-    for i in 0..tiles.len() {
-        let this_pos = idx_to_pos(i, rows, cols).unwrap();
-        for n in neighbors.iter() {
-            if let Some(j) = pos_to_idx(this_pos + n, rows, cols) {
-                if tiles[j].crop != tiles[i].crop {
-                    tiles[i].perimeter += 1;
-                }
-            } else {
-                tiles[i].perimeter += 1;
-            }
-        }
-    }
-    tiles
-}
-
 #[derive(Debug, PartialEq)]
 struct BorderedTile {
     pub crop: char,
@@ -143,100 +76,231 @@ fn parse_bordered_tiles(rows: usize, cols: usize, input: &str) -> Vec<BorderedTi
     tiles
 }
 
-fn part2(input: &str) -> i32 {
-    /* This is not a pretty solution but it works */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Region {
+    pub crop: char,
+    pub tiles: Vec<IVec2>,
+    pub area: usize,
+    pub perimeter: usize,
+    pub sides: usize,
+    /// Bounding box, both corners inclusive
+    pub min: IVec2,
+    pub max: IVec2,
+    /// Areas of other crops that are completely enclosed by this region
+    pub holes: usize,
+}
+
+#[derive(Debug)]
+struct Garden {
+    pub rows: usize,
+    pub cols: usize,
+    /// The index of the region for every tile
+    pub labels: Vec<usize>,
+    pub regions: Vec<Region>,
+}
+
+/// Labels every region with a single flood fill over the map and measures them
+fn analyze(input: &str) -> Garden {
     let (rows, cols) = get_rows_cols(input);
     let tiles = parse_bordered_tiles(rows, cols, input);
     let neighbors = [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y];
 
-    let mut visited = vec![false; tiles.len()];
-    let mut regions: Vec<Vec<IVec2>> = Vec::new();
+    let mut labels = vec![usize::MAX; tiles.len()];
+    let mut regions = Vec::new();
 
     for i in 0..tiles.len() {
-        if visited[i] {
+        if labels[i] != usize::MAX {
             continue;
         }
 
-        let mut region = Vec::new();
+        let label = regions.len();
         let crop = tiles[i].crop;
+        let mut region = Vec::new();
         let mut to_visit = VecDeque::<usize>::new();
         to_visit.push_back(i);
+        labels[i] = label;
 
         while let Some(j) = to_visit.pop_front() {
-            if visited[j] {
-                continue;
-            }
             let this_pos = idx_to_pos(j, rows, cols).unwrap();
-            visited[j] = true;
             region.push(this_pos);
             for n in neighbors
                 .iter()
                 .filter_map(|n| pos_to_idx(n + this_pos, rows, cols))
-                .filter(|k| tiles[*k].crop == crop)
             {
-                to_visit.push_back(n);
+                if labels[n] == usize::MAX && tiles[n].crop == crop {
+                    labels[n] = label;
+                    to_visit.push_back(n);
+                }
             }
         }
         regions.push(region);
     }
 
-    let mut acc = 0;
-    for region in regions.iter() {
-        /* Find the range of rows and cols involved for each region. Because we check each
-         * coordinate individually we create a square over the region, but not all positions are
-         * within the actual region. */
-        let max_col = region.iter().map(|v| v.x).max().unwrap();
-        let min_col = region.iter().map(|v| v.x).min().unwrap();
-        let max_row = region.iter().map(|v| v.y).max().unwrap();
-        let min_row = region.iter().map(|v| v.y).min().unwrap();
-
-        let mut sides = 0;
-        /* Up and left borders */
-        for k in [0, 2] {
-            /* Column major search */
-            for x in min_col..=max_col {
-                let mut inside = false;
-                for y in min_row..=max_row {
-                    let pos = IVec2::new(x, y);
-                    if !region.contains(&pos) {
-                        inside = false;
-                        continue;
-                    }
-                    let idx = pos_to_idx(pos, rows, cols).unwrap();
-                    let has_border = tiles[idx].border[k];
-                    if has_border && !inside {
-                        sides += 1;
-                    }
-                    inside = has_border;
-                }
-            }
-        }
+    let regions = regions
+        .into_iter()
+        .enumerate()
+        .map(|(label, region)| measure(&tiles, &labels, label, region, rows, cols))
+        .collect();
+
+    Garden {
+        rows,
+        cols,
+        labels,
+        regions,
+    }
+}
 
-        /* Down and up borders */
-        for k in [1, 3] {
-            /* Row major search */
-            for y in min_row..=max_row {
-                let mut inside = false;
-                for x in min_col..=max_col {
-                    let pos = IVec2::new(x, y);
-                    if !region.contains(&pos) {
-                        inside = false;
-                        continue;
-                    }
-                    let idx = pos_to_idx(pos, rows, cols).unwrap();
-                    let has_border = tiles[idx].border[k];
-                    if has_border && !inside {
-                        sides += 1;
-                    }
-                    inside = has_border;
-                }
+fn measure(
+    tiles: &[BorderedTile],
+    labels: &[usize],
+    label: usize,
+    region: Vec<IVec2>,
+    rows: usize,
+    cols: usize,
+) -> Region {
+    let inside = |pos: IVec2| pos_to_idx(pos, rows, cols).is_some_and(|i| labels[i] == label);
+    let idx = |pos: IVec2| pos_to_idx(pos, rows, cols).unwrap();
+
+    let perimeter = region
+        .iter()
+        .map(|&p| tiles[idx(p)].border.iter().filter(|&&b| b).count())
+        .sum::<usize>();
+
+    /* A polygon has as many sides as corners. Each tile corner is either convex, with both
+     * neighbours towards the corner outside, or concave, with both inside but not the diagonal. */
+    let mut sides = 0;
+    for &p in region.iter() {
+        for d in [
+            IVec2::ONE,
+            IVec2::NEG_ONE,
+            IVec2::new(1, -1),
+            IVec2::new(-1, 1),
+        ] {
+            let horizontal = inside(p + IVec2::new(d.x, 0));
+            let vertical = inside(p + IVec2::new(0, d.y));
+            let diagonal = inside(p + d);
+            if (!horizontal && !vertical) || (horizontal && vertical && !diagonal) {
+                sides += 1;
             }
         }
+    }
+
+    /* The region is a connected set of squares, so its Euler characteristic V - E + F is
+     * 1 - holes. Inner edges are shared by two tiles and edges on the perimeter belong to one. */
+    let area = region.len();
+    let vertices = region
+        .iter()
+        .flat_map(|&p| [p, p + IVec2::X, p + IVec2::Y, p + IVec2::ONE])
+        .collect::<HashSet<_>>()
+        .len();
+    let edges = (4 * area + perimeter) / 2;
+    let holes = 1 + edges - vertices - area;
+
+    let min = region.iter().copied().reduce(IVec2::min).unwrap();
+    let max = region.iter().copied().reduce(IVec2::max).unwrap();
+
+    Region {
+        crop: tiles[idx(region[0])].crop,
+        tiles: region,
+        area,
+        perimeter,
+        sides,
+        min,
+        max,
+        holes,
+    }
+}
+
+/// Draws every region as a filled polygon with its fences as lines on top. Each region is
+/// labelled with its crop, area, perimeter and sides. `cell` is the size of a tile in pixels.
+/// Escapes a character for use in SVG text
+fn escape_xml(c: char) -> String {
+    match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        c => c.to_string(),
+    }
+}
+
+fn to_svg(garden: &Garden, cell: usize) -> String {
+    let (rows, cols) = (garden.rows, garden.cols);
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {cols} {rows}\">\n",
+        cols * cell,
+        rows * cell
+    );
+
+    for (label, region) in garden.regions.iter().enumerate() {
+        // Spread the hues so neighbouring regions are easy to tell apart
+        let hue = (label * 137) % 360;
+        let path = region
+            .tiles
+            .iter()
+            .map(|p| format!("M{} {}h1v1h-1z", p.x, p.y))
+            .collect::<String>();
+        out += &format!(
+            "<path d=\"{path}\" fill=\"hsl({hue}, 60%, 75%)\"><title>{} area={} perimeter={} sides={}</title></path>\n",
+            escape_xml(region.crop),
+            region.area,
+            region.perimeter,
+            region.sides
+        );
+    }
 
-        acc += sides * region.len();
+    /* A fence runs along every tile edge that borders another region or the edge of the map */
+    let mut fences = String::new();
+    for (i, &label) in garden.labels.iter().enumerate() {
+        let p = idx_to_pos(i, rows, cols).unwrap();
+        let other = |n: IVec2| pos_to_idx(n, rows, cols).is_none_or(|j| garden.labels[j] != label);
+        if other(p + IVec2::NEG_Y) {
+            fences += &format!("M{} {}h1", p.x, p.y);
+        }
+        if other(p + IVec2::Y) {
+            fences += &format!("M{} {}h1", p.x, p.y + 1);
+        }
+        if other(p + IVec2::NEG_X) {
+            fences += &format!("M{} {}v1", p.x, p.y);
+        }
+        if other(p + IVec2::X) {
+            fences += &format!("M{} {}v1", p.x + 1, p.y);
+        }
+    }
+    out += &format!(
+        "<path d=\"{fences}\" fill=\"none\" stroke=\"black\" stroke-width=\"0.1\" stroke-linecap=\"square\"/>\n"
+    );
+
+    for region in garden.regions.iter() {
+        let p = region.tiles[0];
+        out += &format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"0.3\" font-family=\"monospace\">{} {}/{}/{}</text>\n",
+            p.x as f32 + 0.1,
+            p.y as f32 + 0.6,
+            escape_xml(region.crop),
+            region.area,
+            region.perimeter,
+            region.sides
+        );
     }
+    out += "</svg>\n";
+    out
+}
 
-    acc as i32
+fn part1(input: &str) -> i32 {
+    analyze(input)
+        .regions
+        .iter()
+        .map(|r| (r.area * r.perimeter) as i32)
+        .sum()
+}
+
+fn part2(input: &str) -> i32 {
+    analyze(input)
+        .regions
+        .iter()
+        .map(|r| (r.area * r.sides) as i32)
+        .sum()
 }
 
 #[cfg(test)]
@@ -292,6 +356,51 @@ AAAAAA";
         assert_eq!(part2(EX5), 368);
     }
 
+    #[test]
+    fn test_analyze() {
+        let garden = analyze(EX2);
+        assert_eq!(garden.regions.len(), 5);
+        let o = &garden.regions[garden.labels[0]];
+        assert_eq!(o.crop, 'O');
+        assert_eq!((o.area, o.perimeter, o.sides, o.holes), (21, 36, 20, 4));
+        assert_eq!((o.min, o.max), (IVec2::ZERO, IVec2::new(4, 4)));
+        let x = &garden.regions[garden.labels[6]];
+        assert_eq!((x.crop, x.area, x.sides, x.holes), ('X', 1, 4, 0));
+
+        let garden = analyze(EX5);
+        let a = &garden.regions[0];
+        assert_eq!((a.area, a.sides, a.holes), (28, 12, 2));
+
+        let garden = analyze("AAB\nAAB");
+        assert_eq!((garden.rows, garden.cols), (2, 3));
+        assert_eq!(garden.regions[1].min, IVec2::new(2, 0));
+        assert_eq!(garden.regions[1].max, IVec2::new(2, 1));
+    }
+
+    #[test]
+    fn test_to_svg() {
+        let svg = to_svg(&analyze("AB"), 10);
+        assert_eq!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"20\" height=\"10\" viewBox=\"0 0 2 1\">
+<path d=\"M0 0h1v1h-1z\" fill=\"hsl(0, 60%, 75%)\"><title>A area=1 perimeter=4 sides=4</title></path>
+<path d=\"M1 0h1v1h-1z\" fill=\"hsl(137, 60%, 75%)\"><title>B area=1 perimeter=4 sides=4</title></path>
+<path d=\"M0 0h1M0 1h1M0 0v1M1 0v1M1 0h1M1 1h1M1 0v1M2 0v1\" fill=\"none\" stroke=\"black\" stroke-width=\"0.1\" stroke-linecap=\"square\"/>
+<text x=\"0.1\" y=\"0.6\" font-size=\"0.3\" font-family=\"monospace\">A 1/4/4</text>
+<text x=\"1.1\" y=\"0.6\" font-size=\"0.3\" font-family=\"monospace\">B 1/4/4</text>
+</svg>
+"
+        );
+        let svg = to_svg(&analyze(EX3), 20);
+        assert_eq!(svg.matches("<path").count(), 12);
+        assert_eq!(svg.matches("<text").count(), 11);
+
+        let svg = to_svg(&analyze("<&"), 10);
+        assert!(svg.contains("<title>&lt; area=1 perimeter=4 sides=4</title>"));
+        assert!(svg.contains(">&amp; 1/4/4</text>"));
+        assert_eq!(svg.matches('<').count(), svg.matches('>').count());
+    }
+
     #[test]
     fn test_parse_bordered_tiles() {
         let tiles = parse_bordered_tiles(4, 4, EX1);
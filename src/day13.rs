@@ -1,11 +1,27 @@
-use regex::Regex;
+use crate::parse::{self, ParseError};
 
 type IVec2 = glam::I64Vec2;
 
+/// Added to both coordinates of every prize in part 2
+const PART2_OFFSET: i64 = 10000000000000;
+
 pub fn main() {
     let input = std::fs::read_to_string("input/day13.txt").expect("No input");
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    let machines = match parse_input(&input) {
+        Ok(machines) => machines,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+    match part1(&machines) {
+        Some(cost) => println!("Part 1: {cost}"),
+        None => println!("Part 1: total cost overflows"),
+    }
+    match part2(&machines, PART2_OFFSET) {
+        Some(cost) => println!("Part 2: {cost}"),
+        None => println!("Part 2: total cost overflows"),
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -15,80 +31,231 @@ struct ClawMachine {
     target: IVec2,
 }
 
-fn parse_input(input: &str) -> Vec<ClawMachine> {
-    /* This is very unsafe */
-    let re = Regex::new("X.+?(\\d+),\\s+Y.+?(\\d+)").unwrap();
-    let mut machines = Vec::new();
+/// Parses `X+94, Y=-34` style coordinates, in any order and with either `+` or `=`
+fn parse_coordinates(fields: &str, line: usize) -> Result<IVec2, ParseError> {
+    let mut x = None;
+    let mut y = None;
+    for field in fields.split(',').map(str::trim) {
+        let mut chars = field.chars();
+        let axis = match chars.next() {
+            Some('X') => &mut x,
+            Some('Y') => &mut y,
+            _ => return Err(ParseError::new(line, format!("unknown field '{field}'"))),
+        };
+        let value = chars.as_str();
+        let value = value.strip_prefix('=').unwrap_or(value);
+        let value = value
+            .parse::<i64>()
+            .map_err(|_| ParseError::new(line, format!("invalid number in '{field}'")))?;
+        if axis.replace(value).is_some() {
+            return Err(ParseError::new(line, format!("duplicate field '{field}'")));
+        }
+    }
+    match (x, y) {
+        (Some(x), Some(y)) => Ok(IVec2::new(x, y)),
+        _ => Err(ParseError::new(line, "expected both X and Y")),
+    }
+}
 
-    let mut lines = input.lines();
-    while let Some(l) = lines.next() {
-        let a = re
-            .captures(l)
-            .map(|c| IVec2::new(c[1].parse::<i64>().unwrap(), c[2].parse::<i64>().unwrap()))
-            .unwrap();
-        let l = lines.next().unwrap();
-        let b = re
-            .captures(l)
-            .map(|c| IVec2::new(c[1].parse::<i64>().unwrap(), c[2].parse::<i64>().unwrap()))
-            .unwrap();
-        let l = lines.next().unwrap();
-        let target = re
-            .captures(l)
-            .map(|c| IVec2::new(c[1].parse::<i64>().unwrap(), c[2].parse::<i64>().unwrap()))
-            .unwrap();
-        machines.push(ClawMachine { a, b, target });
-        lines.next();
+/// Parses blocks of `Button A`, `Button B` and `Prize` lines separated by blank lines. The lines
+/// in a block may come in any order.
+fn parse_input(input: &str) -> Result<Vec<ClawMachine>, ParseError> {
+    let mut machines = Vec::new();
+    for section in parse::sections(input) {
+        let mut a = None;
+        let mut b = None;
+        let mut target = None;
+        for (i, l) in section.lines.iter().enumerate() {
+            let line = section.line + i;
+            let l = l.trim();
+            let Some((label, fields)) = l.split_once(':') else {
+                return Err(ParseError::new(line, format!("expected a label in '{l}'")));
+            };
+            let field = match label.trim() {
+                "Button A" => &mut a,
+                "Button B" => &mut b,
+                "Prize" => &mut target,
+                label => return Err(ParseError::new(line, format!("unknown label '{label}'"))),
+            };
+            if field.replace(parse_coordinates(fields, line)?).is_some() {
+                return Err(ParseError::new(
+                    line,
+                    format!("duplicate '{}'", label.trim()),
+                ));
+            }
+        }
+        match (a, b, target) {
+            (Some(a), Some(b), Some(target)) => machines.push(ClawMachine { a, b, target }),
+            _ => {
+                return Err(ParseError::new(
+                    section.line,
+                    "incomplete machine, expected Button A, Button B and Prize",
+                ))
+            }
+        }
     }
 
+    Ok(machines)
+}
+
+/// Tokens needed for one press of each button
+#[derive(Debug, Clone, Copy)]
+struct Costs {
+    a: i64,
+    b: i64,
+}
+const PUZZLE_COSTS: Costs = Costs { a: 3, b: 1 };
+
+/// The cost of every prize that can be won, or `None` if it does not fit in an `i64`
+fn total_cost(machines: &[ClawMachine], limit: Option<i64>) -> Option<i64> {
     machines
+        .iter()
+        .try_fold(0_i64, |acc, m| match cheapest(m, PUZZLE_COSTS, limit) {
+            Some((_, cost)) => acc.checked_add(i64::try_from(cost).ok()?),
+            None => Some(acc),
+        })
 }
 
-fn part1(input: &str) -> i64 {
-    let machines = parse_input(input);
-    machines.iter().fold(0, |acc, m| acc + eval_machine(m))
+fn part1(machines: &[ClawMachine]) -> Option<i64> {
+    total_cost(machines, Some(100))
 }
 
-fn eval_machine(machine: &ClawMachine) -> i64 {
-    let max_runs = 100;
-    let mut min_cost = i64::MAX;
-    /* Technically this can go to 2*max_runs */
-    for i in 0..max_runs {
-        for j in 0..max_runs {
-            if machine.a * i + machine.b * j == machine.target {
-                let cost = 3 * i + j;
-                if cost < min_cost {
-                    min_cost = cost;
-                }
-            }
+/// Finds the cheapest number of presses of A and B that reaches the target, with at most
+/// `limit` presses per button. Returns the presses and the total cost, which may not fit in an
+/// `i64`. Costs must not be negative.
+fn cheapest(machine: &ClawMachine, costs: Costs, limit: Option<i64>) -> Option<((i64, i64), i128)> {
+    let (a, b) = solve(machine, costs, limit)?;
+    let cost = costs.a as i128 * a as i128 + costs.b as i128 * b as i128;
+    Some(((a, b), cost))
+}
+
+fn solve(machine: &ClawMachine, costs: Costs, limit: Option<i64>) -> Option<(i64, i64)> {
+    let m = machine;
+    /* The products do not fit in an i64 for large coordinates */
+    let [ax, ay, bx, by, tx, ty] =
+        [m.a.x, m.a.y, m.b.x, m.b.y, m.target.x, m.target.y].map(|v| v as i128);
+    let det = ax * by - ay * bx;
+    let (a, b) = if det != 0 {
+        /* Cramer's rule, there is only one solution */
+        let a = tx * by - ty * bx;
+        let b = ax * ty - ay * tx;
+        if a % det != 0 || b % det != 0 {
+            return None;
         }
+        (i64::try_from(a / det).ok()?, i64::try_from(b / det).ok()?)
+    } else {
+        solve_collinear(machine, costs, limit)?
+    };
+
+    let limit = limit.unwrap_or(i64::MAX);
+    let (pa, pb) = (a as i128, b as i128);
+    if a < 0
+        || b < 0
+        || a > limit
+        || b > limit
+        || pa * ax + pb * bx != tx
+        || pa * ay + pb * by != ty
+    {
+        return None;
     }
-    if min_cost < i64::MAX {
-        return min_cost;
+    Some((a, b))
+}
+
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a.signum() * a, a.signum(), 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
     }
-    0
 }
 
-fn part2(input: &str) -> i64 {
-    let machines = parse_input(input)
+/// Both buttons move the claw along the same line, so the problem reduces to a single axis
+/// `a * i + b * j = t`. The solutions are `i = i0 + k * di` and `j = j0 + k * dj`, and the cost
+/// changes linearly with `k`, so the cheapest solution is at one end of the valid range of `k`.
+/// Whether the target is on the line at all is checked by the caller.
+fn solve_collinear(machine: &ClawMachine, costs: Costs, limit: Option<i64>) -> Option<(i64, i64)> {
+    let m = machine;
+    let (a, b, t) = if m.a.x != 0 || m.b.x != 0 {
+        (m.a.x as i128, m.b.x as i128, m.target.x as i128)
+    } else {
+        (m.a.y as i128, m.b.y as i128, m.target.y as i128)
+    };
+
+    /* If one button does not move the claw at all it is never worth pressing */
+    if a == 0 && b == 0 {
+        return Some((0, 0));
+    } else if a == 0 {
+        return (t % b == 0).then(|| (0, (t / b) as i64));
+    } else if b == 0 {
+        return (t % a == 0).then(|| ((t / a) as i64, 0));
+    }
+
+    let (g, x, y) = extended_gcd(a, b);
+    if t % g != 0 {
+        return None;
+    }
+    let (i0, j0) = (x * (t / g), y * (t / g));
+    let (di, dj) = (b / g, -a / g);
+
+    /* Collect the range of k that keeps both press counts within 0..=limit */
+    let mut k_min = i128::MIN;
+    let mut k_max = i128::MAX;
+    let limit = limit.map_or(i128::MAX, |l| l as i128);
+    for (p0, dp) in [(i0, di), (j0, dj)] {
+        // p0 + k * dp >= 0 and p0 + k * dp <= limit
+        let (low, high) = if dp > 0 {
+            (div_ceil(-p0, dp), div_floor(limit.saturating_sub(p0), dp))
+        } else {
+            (div_ceil(limit.saturating_sub(p0), dp), div_floor(-p0, dp))
+        };
+        k_min = k_min.max(low);
+        k_max = k_max.min(high);
+    }
+    if k_min > k_max {
+        return None;
+    }
+
+    let slope = costs.a as i128 * di + costs.b as i128 * dj;
+    let k = if slope > 0 || k_max == i128::MAX {
+        k_min
+    } else {
+        k_max
+    };
+    let (i, j) = (i0 + k * di, j0 + k * dj);
+    Some((i64::try_from(i).ok()?, i64::try_from(j).ok()?))
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -div_floor(-a, b)
+}
+
+/// `None` if a moved prize or the total cost does not fit in an `i64`
+fn part2(machines: &[ClawMachine], offset: i64) -> Option<i64> {
+    let machines = machines
         .iter()
-        .map(|m| ClawMachine {
-            a: m.a,
-            b: m.b,
-            target: m.target + 10000000000000,
+        .map(|m| {
+            Some(ClawMachine {
+                a: m.a,
+                b: m.b,
+                target: IVec2::new(
+                    m.target.x.checked_add(offset)?,
+                    m.target.y.checked_add(offset)?,
+                ),
+            })
         })
-        .collect::<Vec<ClawMachine>>();
-
-    let mut cost = 0;
-    for m in machines {
-        let det = (m.a.x * m.b.y) - (m.a.y * m.b.x);
-        let a = (m.target.x * m.b.y - m.target.y * m.b.x) / det;
-        let b = (m.a.x * m.target.y - m.a.y * m.target.x) / det;
-        if a * m.a + b * m.b == m.target {
-            cost += 3 * a + b;
-        }
-    }
+        .collect::<Option<Vec<ClawMachine>>>()?;
 
-    cost
+    total_cost(&machines, None)
 }
 
 #[cfg(test)]
@@ -113,7 +280,7 @@ Prize: X=18641, Y=10279";
 
     #[test]
     fn test_parse_input() {
-        let parsed = parse_input(INPUT);
+        let parsed = parse_input(INPUT).unwrap();
         assert_eq!(
             parsed[0],
             ClawMachine {
@@ -126,6 +293,130 @@ Prize: X=18641, Y=10279";
 
     #[test]
     fn test_part1() {
-        assert_eq!(part1(INPUT), 480);
+        assert_eq!(part1(&parse_input(INPUT).unwrap()), Some(480));
+    }
+
+    #[test]
+    fn test_part2() {
+        let machines = parse_input(INPUT).unwrap();
+        assert_eq!(part2(&machines, PART2_OFFSET), Some(875318608908));
+        assert_eq!(part2(&machines, 0), Some(480));
+        assert_eq!(part2(&machines, i64::MAX), None);
+        // Each prize costs i64::MAX, so only the sum overflows
+        let big = || machine((1, 0), (0, 1), (0, i64::MAX));
+        assert_eq!(total_cost(&[big()], None), Some(i64::MAX));
+        assert_eq!(total_cost(&[big(), big()], None), None);
+    }
+
+    #[test]
+    fn test_parse_tolerant() {
+        let input =
+            "\r\nPrize: Y=5400, X=8400\r\nButton B: X+22, Y+67\r\nButton A: X+94, Y+34\r\n\r\n\r\n\
+                     Button A: X-1, Y=+2\nButton B: X+0, Y-3\nPrize: X=-4, Y=5\n\n\n";
+        let parsed = parse_input(input).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0], parse_input(INPUT).unwrap()[0]);
+        assert_eq!(
+            parsed[1],
+            ClawMachine {
+                a: IVec2::new(-1, 2),
+                b: IVec2::new(0, -3),
+                target: IVec2::new(-4, 5)
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_input("Button A: X+1, Y+2\nButton B: X+1, Y+2\n\nPrize: X=1, Y=2"),
+            Err(ParseError::new(
+                1,
+                "incomplete machine, expected Button A, Button B and Prize"
+            ))
+        );
+        assert_eq!(
+            parse_input("Button A: X+1, Y+2\nButton B: X+1, Z+2").unwrap_err(),
+            ParseError::new(2, "unknown field 'Z+2'")
+        );
+        assert_eq!(
+            parse_input("\n\nButton A: X+1, Y+x").unwrap_err(),
+            ParseError::new(3, "invalid number in 'Y+x'")
+        );
+        assert_eq!(
+            parse_input("Button A: X+1, Y+2\nButton A: X+1, Y+2").unwrap_err(),
+            ParseError::new(2, "duplicate 'Button A'")
+        );
+        assert_eq!(
+            parse_input("Button C: X+1, Y+2").unwrap_err().to_string(),
+            "line 1: unknown label 'Button C'"
+        );
+        assert_eq!(
+            parse_input("Prize: X=1").unwrap_err(),
+            ParseError::new(1, "expected both X and Y")
+        );
+    }
+
+    fn machine(a: (i64, i64), b: (i64, i64), target: (i64, i64)) -> ClawMachine {
+        ClawMachine {
+            a: IVec2::new(a.0, a.1),
+            b: IVec2::new(b.0, b.1),
+            target: IVec2::new(target.0, target.1),
+        }
+    }
+
+    #[test]
+    fn test_cheapest() {
+        let m = machine((94, 34), (22, 67), (8400, 5400));
+        assert_eq!(cheapest(&m, PUZZLE_COSTS, Some(100)), Some(((80, 40), 280)));
+        assert_eq!(cheapest(&m, PUZZLE_COSTS, Some(79)), None);
+        let costs = Costs { a: 1, b: 1 };
+        assert_eq!(cheapest(&m, costs, None), Some(((80, 40), 120)));
+
+        // Cramer's rule overflows an i64 here, and so does the cost below
+        let big = i64::MAX / 2;
+        let m = machine((big, 0), (0, big), (big * 2, big));
+        assert_eq!(solve(&m, PUZZLE_COSTS, None), Some((2, 1)));
+        assert_eq!(cheapest(&m, PUZZLE_COSTS, None), Some(((2, 1), 7)));
+        let m = machine((1, 0), (0, 1), (big, big));
+        assert_eq!(
+            cheapest(&m, PUZZLE_COSTS, None),
+            Some(((big, big), 4 * big as i128))
+        );
+        assert_eq!(total_cost(&[m], None), None);
+    }
+
+    #[test]
+    fn test_collinear() {
+        let m = machine((1, 1), (2, 2), (4, 4));
+        assert_eq!(cheapest(&m, PUZZLE_COSTS, None), Some(((0, 2), 2)));
+        let costs = Costs { a: 1, b: 3 };
+        assert_eq!(cheapest(&m, costs, None), Some(((4, 0), 4)));
+        assert_eq!(cheapest(&m, costs, Some(3)), Some(((2, 1), 5)));
+        assert_eq!(cheapest(&m, costs, Some(1)), None);
+
+        let m = machine((4, 6), (6, 9), (14, 21));
+        assert_eq!(cheapest(&m, PUZZLE_COSTS, None), Some(((2, 1), 7)));
+        assert_eq!(
+            solve(&machine((4, 6), (6, 9), (15, 21)), PUZZLE_COSTS, None),
+            None
+        );
+        assert_eq!(
+            solve(&machine((1, 1), (2, 2), (4, 5)), PUZZLE_COSTS, None),
+            None
+        );
+
+        assert_eq!(
+            solve(&machine((0, 0), (0, 3), (0, 9)), PUZZLE_COSTS, None),
+            Some((0, 3))
+        );
+        assert_eq!(
+            solve(&machine((0, 0), (0, 0), (0, 0)), PUZZLE_COSTS, None),
+            Some((0, 0))
+        );
+        assert_eq!(
+            solve(&machine((0, 0), (0, 0), (1, 0)), PUZZLE_COSTS, None),
+            None
+        );
     }
 }
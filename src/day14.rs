@@ -2,20 +2,46 @@ use glam::IVec2;
 use std::io;
 
 use ratatui::{
-    crossterm::event::{self, KeyCode, KeyEventKind},
+    crossterm::{
+        event::{self, KeyCode, KeyEventKind},
+        style::Color,
+    },
+    layout::{Constraint, Layout},
     style::Stylize,
     widgets::Paragraph,
 };
 
+use crate::export;
+use crate::parse::{self, ParseError};
+use crate::render::{Frame, Highlight};
+
 pub fn main() {
     let input = std::fs::read_to_string("input/day14.txt").expect("No input");
+    let robots = match parse_input(&input) {
+        Ok(robots) => robots,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
     let rows = 103;
     let cols = 101;
-    println!("Part 1: {}", part1(&input, rows, cols));
-    let _ = part2(&input);
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("export") {
+        // `cargo run 14 export out=tree.gif skip=7000 limit=50 cell=2` exports seconds 7000 to
+        // 7049. See `export::Options` for the rest.
+        let defaults = export::Options {
+            limit: Some(100),
+            ..export::Options::new("day14.gif")
+        };
+        export::run(&args[3..], defaults, frames(robots, rows, cols));
+        return;
+    }
+    println!("Part 1: {}", part1(&robots, rows, cols));
+    let _ = part2(robots);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Robot {
     pub pos: IVec2,
     pub vel: IVec2,
@@ -26,17 +52,23 @@ impl Robot {
     }
 }
 
-fn parse_input(input: &str) -> Vec<Robot> {
-    input.lines().filter_map(parse_single).collect()
+fn parse_input(input: &str) -> Result<Vec<Robot>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| parse_single(l, i + 1))
+        .collect()
 }
 
-fn parse_single(line: &str) -> Option<Robot> {
-    let (p, v) = line.split_once(' ')?;
-    let pos = p.split_once('=')?.1.split_once(',')?;
-    let pos = IVec2::new(pos.0.parse::<i32>().ok()?, pos.1.parse::<i32>().ok()?);
-    let vel = v.split_once('=')?.1.split_once(',')?;
-    let vel = IVec2::new(vel.0.parse::<i32>().ok()?, vel.1.parse::<i32>().ok()?);
-    Some(Robot::new(pos, vel))
+/// Parses `p=0,4 v=3,-3`
+fn parse_single(line: &str, line_nr: usize) -> Result<Robot, ParseError> {
+    let fields = parse::key_values(line, line_nr)?;
+    let vec2 = |key| -> Result<IVec2, ParseError> {
+        let (x, y) = parse::pair(parse::value(&fields, key, line_nr)?, ',', line_nr)?;
+        Ok(IVec2::new(x, y))
+    };
+    Ok(Robot::new(vec2("p")?, vec2("v")?))
 }
 
 fn step(robots: &mut [Robot], rows: usize, cols: usize, steps: i32) {
@@ -70,16 +102,25 @@ fn safety_factor(robots: &[Robot], rows: usize, cols: usize) -> i32 {
     quadrants[0] * quadrants[1] * quadrants[2] * quadrants[3]
 }
 
-fn part1(input: &str, rows: usize, cols: usize) -> i32 {
-    let mut robots = parse_input(input);
+/// Every second from the start, forever
+fn frames(robots: Vec<Robot>, rows: usize, cols: usize) -> impl Iterator<Item = Frame> {
+    std::iter::successors(Some(robots), move |robots| {
+        let mut robots = robots.clone();
+        step(&mut robots, rows, cols, 1);
+        Some(robots)
+    })
+    .map(move |robots| create_sprite(&robots, rows, cols))
+}
+
+fn part1(robots: &[Robot], rows: usize, cols: usize) -> i32 {
+    let mut robots = robots.to_vec();
     for _ in 0..100 {
         step(&mut robots, rows, cols, 1);
     }
     safety_factor(&robots, rows, cols)
 }
 
-fn part2(input: &str) -> io::Result<()> {
-    let mut robots = parse_input(input);
+fn part2(mut robots: Vec<Robot>) -> io::Result<()> {
     let mut steps = 0;
     let rows = 103;
     let cols = 101;
@@ -88,11 +129,11 @@ fn part2(input: &str) -> io::Result<()> {
     terminal.clear()?;
     loop {
         terminal.draw(|frame| {
-            let ascii_art = create_sprite(&robots, rows, cols);
-            let greeting =
-                Paragraph::new(format!("Steps: {steps} --- Press q to quit\n{ascii_art}")).red();
-            // let info = Paragraph::new("Steps: {steps}").green().on_red();
-            frame.render_widget(greeting, frame.area());
+            let [header, area] =
+                Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(frame.area());
+            let greeting = Paragraph::new(format!("Steps: {steps} --- Press q to quit")).red();
+            frame.render_widget(greeting, header);
+            frame.render_widget(create_sprite(&robots, rows, cols).to_widget(), area);
         })?;
 
         if let event::Event::Key(key) = event::read()? {
@@ -113,24 +154,9 @@ fn part2(input: &str) -> io::Result<()> {
     Ok(())
 }
 
-fn pos_to_idx(pos: IVec2, rows: usize, cols: usize) -> Option<usize> {
-    if pos.x >= cols as i32 || pos.y >= rows as i32 || pos.x < 0 || pos.y < 0 {
-        return None;
-    }
-    Some((pos.y * (cols as i32) + pos.x) as usize)
-}
-
-fn create_sprite(robots: &[Robot], rows: usize, cols: usize) -> String {
-    let mut buffer = vec![' '; rows * cols];
-    for r in robots {
-        let idx = pos_to_idx(r.pos, rows, cols).unwrap();
-        buffer[idx] = '#';
-    }
-    buffer
-        .chunks(cols)
-        .map(|chunk| chunk.iter().collect::<String>())
-        .collect::<Vec<_>>()
-        .join("\n")
+fn create_sprite(robots: &[Robot], rows: usize, cols: usize) -> Frame {
+    let positions = robots.iter().map(|r| r.pos).collect::<Vec<_>>();
+    Frame::new(cols, rows, ' ').layer(&Highlight::new(&positions, '#').color(Color::Red))
 }
 
 #[cfg(test)]
@@ -154,7 +180,39 @@ p=9,5 v=-3,-3";
     fn test_part1() {
         let rows = 7;
         let cols = 11;
-        assert_eq!(part1(INPUT, rows, cols), 12)
+        assert_eq!(part1(&parse_input(INPUT).unwrap(), rows, cols), 12)
+    }
+
+    #[test]
+    fn test_parse_input() {
+        let robots = parse_input("p=0,4 v=3,-3\n\np=6,3 v=-1,-3\n").unwrap();
+        assert_eq!(robots[1].pos, IVec2::new(6, 3));
+        assert_eq!(robots[1].vel, IVec2::new(-1, -3));
+        assert_eq!(
+            parse_input("p=0,4 v=3,-3\np=6,3 w=-1,-3")
+                .unwrap_err()
+                .to_string(),
+            "line 2: missing key 'v'"
+        );
+    }
+
+    #[test]
+    fn test_create_sprite() {
+        let robots = vec![
+            Robot::new(IVec2::new(0, 0), IVec2::ZERO),
+            Robot::new(IVec2::new(2, 1), IVec2::ZERO),
+        ];
+        assert_eq!(create_sprite(&robots, 2, 3).to_text(), "#  \n  #");
+    }
+
+    #[test]
+    fn test_frames() {
+        let robots = vec![Robot::new(IVec2::new(0, 0), IVec2::new(1, 0))];
+        let frames = frames(robots, 1, 2)
+            .take(3)
+            .map(|f| f.to_text())
+            .collect::<Vec<_>>();
+        assert_eq!(frames, ["# ", " #", "# "]);
     }
 
     #[test]
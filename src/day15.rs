@@ -1,7 +1,34 @@
+use crossterm::style::Color;
 use glam::IVec2;
 
+use crate::export;
+use crate::render::{Frame, Render};
+
 pub fn main() {
     let input = std::fs::read_to_string("input/day15.txt").expect("No input");
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(2).map(String::as_str) {
+        Some("show") => {
+            // `cargo run 15 show` prints the warehouse after the robot is done
+            let (mut warehouse, directions) = parse_input(&input);
+            for d in directions.into_iter() {
+                step(&mut warehouse, d);
+            }
+            println!("{}", warehouse.frame().to_ansi());
+            return;
+        }
+        Some("export") => {
+            // `cargo run 15 export out=day15.gif every=50` keeps one frame per 50 moves
+            let (warehouse, directions) = parse_input(&input);
+            let defaults = export::Options {
+                every: 20,
+                ..export::Options::new("day15.gif")
+            };
+            export::run(&args[3..], defaults, frames(warehouse, directions));
+            return;
+        }
+        _ => {}
+    }
     println!("Part 1: {}", part1(&input));
     // println!("Part 2: {}", part2(&input));
 }
@@ -20,34 +47,26 @@ impl Warehouse {
             robot: IVec2::ZERO,
         }
     }
-}
-impl core::fmt::Display for Warehouse {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn frame(&self) -> Frame {
         let cols = self.walls.iter().map(|v| v.x).max().unwrap() as usize + 1;
         let rows = self.walls.iter().map(|v| v.y).max().unwrap() as usize + 1;
-
-        let mut buffer = vec!['.'; rows * cols];
+        Frame::new(cols, rows, '.').layer(self)
+    }
+}
+impl Render for Warehouse {
+    fn render(&self, frame: &mut Frame) {
         for w in self.walls.iter() {
-            if let Some(idx) = pos_to_idx(w, rows, cols) {
-                buffer[idx] = '#';
-            }
+            frame.set(*w, '#', Some(Color::DarkGrey));
         }
         for b in self.boxes.iter() {
-            if let Some(idx) = pos_to_idx(b, rows, cols) {
-                buffer[idx] = 'O';
-            }
+            frame.set(*b, 'O', Some(Color::Yellow));
         }
-        if let Some(idx) = pos_to_idx(&self.robot, rows, cols) {
-            buffer[idx] = '@';
-        }
-
-        let s = buffer
-            .chunks(cols)
-            .map(|chunk| chunk.iter().collect::<String>())
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        write!(f, "{}", s)
+        frame.set(self.robot, '@', Some(Color::Red));
+    }
+}
+impl core::fmt::Display for Warehouse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.frame().to_text())
     }
 }
 
@@ -60,13 +79,6 @@ enum Direction {
 }
 type Directions = Vec<Direction>;
 
-fn pos_to_idx(pos: &IVec2, rows: usize, cols: usize) -> Option<usize> {
-    if pos.x >= cols as i32 || pos.y >= rows as i32 || pos.x < 0 || pos.y < 0 {
-        return None;
-    }
-    Some((pos.y * (cols as i32) + pos.x) as usize)
-}
-
 fn parse_input(input: &str) -> (Warehouse, Directions) {
     let mut warehouse = Warehouse::new();
     let mut directions = Vec::new();
@@ -133,6 +145,15 @@ fn step(warehouse: &mut Warehouse, d: Direction) {
     }
 }
 
+/// The warehouse before the first move and after every move
+fn frames(mut warehouse: Warehouse, directions: Directions) -> impl Iterator<Item = Frame> {
+    let first = warehouse.frame();
+    std::iter::once(first).chain(directions.into_iter().map(move |d| {
+        step(&mut warehouse, d);
+        warehouse.frame()
+    }))
+}
+
 fn part1(input: &str) -> i32 {
     let (mut warehouse, directions) = parse_input(input);
     for d in directions.into_iter() {
@@ -206,6 +227,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_display() {
+        let (warehouse, _) = parse_input("####\n#@O#\n#..#\n####");
+        assert_eq!(warehouse.to_string(), "####\n#@O#\n#..#\n####");
+    }
+
+    #[test]
+    fn test_frames() {
+        let (warehouse, directions) = parse_input("####\n#@.#\n####\n\n>>");
+        let frames = frames(warehouse, directions)
+            .map(|f| f.to_text())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frames,
+            ["####\n#@.#\n####", "####\n#.@#\n####", "####\n#.@#\n####"]
+        );
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(INPUT), 2028);
@@ -1,11 +1,35 @@
 use core::fmt;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use crossterm::style::Color;
 use glam::IVec2;
 use itertools::Itertools;
 
+use crate::export;
+use crate::render::{Frame, Highlight, Render};
+
 pub fn main() {
     let input = std::fs::read_to_string("input/day16.txt").expect("No input");
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(2).map(String::as_str) {
+        Some("path") => {
+            // `cargo run 16 path` shows every tile on a best path
+            let maze = parse_input(&input);
+            debug_print_visited(&maze, &best_tiles(&maze));
+            return;
+        }
+        Some("export") => {
+            // `cargo run 16 export out=day16.gif every=5`
+            let maze = parse_input(&input);
+            let defaults = export::Options {
+                every: maze.cols,
+                ..export::Options::new("day16.gif")
+            };
+            export::run(&args[3..], defaults, search_frames(&maze));
+            return;
+        }
+        _ => {}
+    }
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
 }
@@ -37,19 +61,27 @@ impl Maze {
         let idx = pos_to_idx(pos, self.rows, self.cols)?;
         Some(self.tiles[idx])
     }
+
+    fn frame(&self) -> Frame {
+        Frame::new(self.cols, self.rows, '.').layer(self)
+    }
+}
+impl Render for Maze {
+    fn render(&self, frame: &mut Frame) {
+        for (idx, &c) in self.tiles.iter().enumerate() {
+            let pos = IVec2::new((idx % self.cols) as i32, (idx / self.cols) as i32);
+            let color = match c {
+                '#' => Some(Color::DarkGrey),
+                'S' | 'E' => Some(Color::Green),
+                _ => None,
+            };
+            frame.set(pos, c, color);
+        }
+    }
 }
 impl fmt::Display for Maze {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut out = String::with_capacity(self.tiles.len() + self.rows);
-        let mut idx = 0;
-        for _ in 0..self.rows {
-            for _ in 0..self.cols {
-                out.push(self.tiles[idx]);
-                idx += 1;
-            }
-            out.push('\n');
-        }
-        write!(f, "{}", out)
+        writeln!(f, "{}", self.frame().to_text())
     }
 }
 
@@ -94,7 +126,7 @@ enum Direction {
     North,
 }
 impl Direction {
-    fn to_vec(&self) -> IVec2 {
+    fn to_vec(self) -> IVec2 {
         match self {
             Direction::East => IVec2::NEG_X,
             Direction::South => IVec2::Y,
@@ -130,7 +162,7 @@ impl TileToVisit {
     fn new(pos: IVec2, facing: Direction, cost: i32) -> Self {
         Self { pos, facing, cost }
     }
-    fn to_tile(&self) -> Tile {
+    fn to_tile(self) -> Tile {
         Tile {
             pos: self.pos,
             facing: self.facing,
@@ -149,6 +181,12 @@ impl Tile {
 }
 
 fn walk(maze: &Maze) -> HashMap<Tile, i32> {
+    search(maze, |_| {})
+}
+
+/// Finds the cheapest cost to every tile and direction. `visit` is called with each position
+/// the search reaches, in the order it gets there.
+fn search(maze: &Maze, mut visit: impl FnMut(IVec2)) -> HashMap<Tile, i32> {
     let mut frontier = Vec::new();
     frontier.push(TileToVisit::new(maze.start, Direction::East, 0));
     let mut reached = HashMap::new();
@@ -161,6 +199,7 @@ fn walk(maze: &Maze) -> HashMap<Tile, i32> {
         }
 
         reached.insert(Tile::new(current.pos, current.facing), current.cost);
+        visit(current.pos);
         if let Some('E') = maze.tile(current.pos) {
             break;
         }
@@ -184,7 +223,7 @@ fn walk(maze: &Maze) -> HashMap<Tile, i32> {
             current.cost + 1000,
         ));
 
-        frontier.sort_by(|x, y| y.cost.cmp(&x.cost));
+        frontier.sort_by_key(|x| std::cmp::Reverse(x.cost));
     }
     reached
 }
@@ -235,34 +274,45 @@ fn backtrack(reached: &HashMap<Tile, i32>, maze: &Maze, lowest_cost: i32) -> Vec
     trail
 }
 
+/// Every position on at least one of the cheapest paths
+fn best_tiles(maze: &Maze) -> Vec<IVec2> {
+    let reached = walk(maze);
+    let cost = get_cost(&reached, maze);
+    let trail = backtrack(&reached, maze, cost);
+    trail.iter().map(|t| t.pos).unique().collect()
+}
+
 fn part2(input: &str) -> i32 {
     let maze = parse_input(input);
-    let reached = walk(&maze);
-
-    let cost = get_cost(&reached, &maze);
-
-    let trail = backtrack(&reached, &maze, cost);
-    let trail = trail.iter().map(|t| t.pos).unique().collect::<Vec<_>>();
-
-    // debug_print_visited(maze, &trail);
-
-    trail.len() as i32
+    best_tiles(&maze).len() as i32
 }
 
-#[allow(dead_code)]
-fn debug_print_visited(maze: Maze, trail: &[IVec2]) {
-    for y in 0..maze.rows {
-        for x in 0..maze.cols {
-            let pos = IVec2::new(x as i32, y as i32);
-            let c = if trail.contains(&pos) {
-                'O'
-            } else {
-                maze.tiles[pos_to_idx(pos, maze.rows, maze.cols).unwrap()]
-            };
-            print!("{}", c);
+/// One frame for every new position the search reaches, then the best paths
+fn search_frames(maze: &Maze) -> impl Iterator<Item = Frame> + '_ {
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    search(maze, |pos| {
+        if seen.insert(pos) {
+            order.push(pos);
         }
-        println!("");
-    }
+    });
+    let best = best_tiles(maze);
+    (1..=order.len())
+        .map(move |n| {
+            maze.frame()
+                .layer(&Highlight::new(&order[..n], 'o').color(Color::DarkYellow))
+        })
+        .chain(std::iter::once_with(move || {
+            maze.frame()
+                .layer(&Highlight::new(&best, 'O').color(Color::Red))
+        }))
+}
+
+fn debug_print_visited(maze: &Maze, trail: &[IVec2]) {
+    let frame = maze
+        .frame()
+        .layer(&Highlight::new(trail, 'O').color(Color::Red));
+    println!("{}", frame.to_ansi());
 }
 
 #[cfg(test)]
@@ -308,6 +358,32 @@ mod tests {
         assert_eq!(part1(INPUT), 7036);
     }
 
+    #[test]
+    fn test_display() {
+        let maze = parse_input("####\n#SE#\n####");
+        assert_eq!(maze.to_string(), "####\n#SE#\n####\n");
+    }
+
+    #[test]
+    fn test_search_frames() {
+        let maze = parse_input("#####\n#..E#\n#S..#\n#####");
+        let frames = search_frames(&maze)
+            .map(|f| f.to_text())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frames,
+            [
+                "#####\n#..E#\n#o..#\n#####",
+                "#####\n#o.E#\n#o..#\n#####",
+                "#####\n#o.E#\n#oo.#\n#####",
+                "#####\n#ooE#\n#oo.#\n#####",
+                "#####\n#ooE#\n#ooo#\n#####",
+                "#####\n#ooo#\n#ooo#\n#####",
+                "#####\n#OOO#\n#O..#\n#####",
+            ]
+        );
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(INPUT), 45);
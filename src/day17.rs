@@ -1,5 +1,7 @@
 use itertools::Itertools;
 
+use crate::parse::{self, ParseError};
+
 pub fn main() {
     let input = std::fs::read_to_string("input/day17.txt").expect("No input");
     println!("Part 1: {}", part1(&input));
@@ -15,25 +17,26 @@ struct Program {
     ins: Vec<i64>,
 }
 impl Program {
-    pub fn from_str(input: &str) -> Option<Self> {
-        let mut l = input.lines();
-        let a = l.next()?.split_once(": ")?.1.parse::<i64>().ok()?;
-        let b = l.next()?.split_once(": ")?.1.parse::<i64>().ok()?;
-        let c = l.next()?.split_once(": ")?.1.parse::<i64>().ok()?;
-        l.next();
-        let ins = l
-            .next()?
-            .split_once(": ")?
-            .1
-            .split(',')
-            .filter_map(|s| s.parse::<i64>().ok())
-            .collect();
-        Some(Program {
-            a,
-            b,
-            c,
+    pub fn from_str(input: &str) -> Result<Self, ParseError> {
+        let lines = input.lines().collect::<Vec<_>>();
+        let values = |i: usize| -> Result<Vec<i64>, ParseError> {
+            let line = lines
+                .get(i)
+                .ok_or_else(|| ParseError::new(i + 1, "missing line"))?;
+            parse::ints(line, i + 1)
+        };
+        let register = |i: usize| -> Result<i64, ParseError> {
+            match values(i)?[..] {
+                [value] => Ok(value),
+                _ => Err(ParseError::new(i + 1, "expected a single register value")),
+            }
+        };
+        Ok(Program {
+            a: register(0)?,
+            b: register(1)?,
+            c: register(2)?,
             iptr: 0,
-            ins,
+            ins: values(4)?,
         })
     }
     #[allow(unused)]
@@ -78,39 +81,39 @@ fn eval(p: &mut Program) -> Vec<i64> {
         match opcode {
             0 => {
                 // adv
-                p.a = p.a / 2_i64.pow(combo_operand(&p) as u32);
+                p.a /= 2_i64.pow(combo_operand(p) as u32);
             }
             1 => {
                 // bxl
-                p.b = p.b ^ literal_operand(&p);
+                p.b ^= literal_operand(p);
             }
             2 => {
                 // bst
-                p.b = combo_operand(&p).rem_euclid(8)
+                p.b = combo_operand(p).rem_euclid(8)
             }
             3 => {
                 // jnz
                 if p.a > 0 {
-                    p.iptr = literal_operand(&p) as usize;
+                    p.iptr = literal_operand(p) as usize;
                     continue;
                 }
             }
             4 => {
                 // bxc
-                p.b = p.b ^ p.c;
+                p.b ^= p.c;
             }
             5 => {
                 // out
-                let res = combo_operand(&p).rem_euclid(8);
+                let res = combo_operand(p).rem_euclid(8);
                 out.push(res);
             }
             6 => {
                 // bdv
-                p.b = p.a / 2_i64.pow(combo_operand(&p) as u32);
+                p.b = p.a / 2_i64.pow(combo_operand(p) as u32);
             }
             7 => {
                 // cdv
-                p.c = p.a / 2_i64.pow(combo_operand(&p) as u32);
+                p.c = p.a / 2_i64.pow(combo_operand(p) as u32);
             }
             _ => panic!("invalid opcode"),
         };
@@ -181,9 +184,16 @@ Program: 0,1,5,4,3,0";
         assert_eq!(p.b, 0);
         assert_eq!(p.c, 0);
         assert_eq!(p.ins, vec![0, 1, 5, 4, 3, 0]);
+        assert_eq!(
+            Program::from_str("Register A: 729\nRegister B:\n")
+                .unwrap_err()
+                .to_string(),
+            "line 2: expected a single register value"
+        );
     }
 
     #[test]
+    #[allow(clippy::identity_op)]
     fn test_opcodes() {
         assert_eq!(3 ^ 7, 4);
         assert_eq!(0 ^ 1, 1);
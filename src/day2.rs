@@ -1,78 +1,198 @@
+use itertools::Itertools;
+
+use crate::parse::{self, ParseError};
+
 pub fn main() {
     let input = std::fs::read_to_string("input/day2.txt").expect("No input");
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
-}
-
-fn is_safe(report: &[i32]) -> bool {
-    let sign = (report[0] - report[1]).signum();
-    if sign == 0 {
-        return false;
-    }
-    for w in report.windows(2) {
-        let diff = w[0] - w[1];
-        if diff.signum() != sign {
-            return false;
+    let reports = match parse_input(&input) {
+        Ok(reports) => reports,
+        Err(e) => {
+            println!("{e}");
+            return;
         }
-        if diff.abs() > 3 {
-            return false;
+    };
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("explain") {
+        // `cargo run 2 explain 2 1 3` allows two removals and steps between 1 and 3
+        let arg = |i: usize, default| args.get(i).and_then(|s| s.parse().ok()).unwrap_or(default);
+        let dampener = Dampener {
+            max_removals: args.get(3).and_then(|s| s.parse().ok()).unwrap_or(1),
+            min_step: arg(4, 1),
+            max_step: arg(5, 3),
+        };
+        for report in reports.iter() {
+            println!("{}: {}", report.iter().join(" "), check(report, &dampener));
         }
+        return;
     }
-    true
+    println!("Part 1: {}", part1(&reports));
+    println!("Part 2: {}", part2(&reports));
 }
 
-fn part1(input: &str) -> i32 {
-    let mut acc = 0;
-    for line in input.lines() {
-        let report = line
-            .split_whitespace()
-            .filter_map(|s| s.parse::<i32>().ok())
-            .collect::<Vec<i32>>();
-        if is_safe(&report) {
-            acc += 1;
+/// How many levels may be removed and how much adjacent levels may differ
+#[derive(Debug, Clone, Copy)]
+struct Dampener {
+    max_removals: usize,
+    min_step: i32,
+    max_step: i32,
+}
+const PART1: Dampener = Dampener {
+    max_removals: 0,
+    min_step: 1,
+    max_step: 3,
+};
+const PART2: Dampener = Dampener {
+    max_removals: 1,
+    ..PART1
+};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Violation {
+    Unchanged,
+    DirectionChanged,
+    StepTooSmall(i32),
+    StepTooLarge(i32),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Check {
+    /// Safe after removing the levels at these indices
+    Safe { removed: Vec<usize> },
+    /// The first pair of levels that breaks the rules, ending at `index`
+    Unsafe { index: usize, violation: Violation },
+}
+impl Check {
+    fn is_safe(&self) -> bool {
+        matches!(self, Check::Safe { .. })
+    }
+}
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Check::Safe { removed } if removed.is_empty() => write!(f, "Safe"),
+            Check::Safe { removed } => write!(f, "Safe by removing level(s) {removed:?}"),
+            Check::Unsafe { index, violation } => {
+                let reason = match violation {
+                    Violation::Unchanged => "is unchanged".to_string(),
+                    Violation::DirectionChanged => "changes direction".to_string(),
+                    Violation::StepTooSmall(d) => format!("changes too little ({d})"),
+                    Violation::StepTooLarge(d) => format!("changes too much ({d})"),
+                };
+                write!(f, "Unsafe, level {index} {reason}")
+            }
         }
     }
-    acc
 }
 
-fn is_safe_with_tolerance(report: &[i32]) -> bool {
-    match is_safe(report) {
-        true => return true,
-        false => {
-            for j in 0..report.len() {
-                let report = Vec::from_iter(
-                    report
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(i, val)| {
-                            if i != j {
-                                return Some(val);
-                            }
-                            None
-                        })
-                        .cloned(),
-                );
-                if is_safe(&report) {
-                    return true;
+/// One report per line. Blank lines are skipped, rather than being empty reports that would count
+/// as safe.
+fn parse_input(input: &str) -> Result<Vec<Vec<i32>>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| parse::fields(l, i + 1))
+        .collect()
+}
+
+/// Finds the fewest levels to remove for the report to be safe, in either direction.
+///
+/// For each direction `removals[i]` is the fewest removals among the first `i` levels if level
+/// `i` is kept. Only the `max_removals + 1` levels before `i` can be the previous kept level, so
+/// this is a single pass over the report for a fixed number of removals. On ties the earlier
+/// levels are removed.
+fn check(report: &[i32], dampener: &Dampener) -> Check {
+    let n = report.len();
+    // Removing more levels than the report has makes no difference, and keeps `k + 1` in range
+    let k = dampener.max_removals.min(n);
+    let mut best: Option<Vec<usize>> = None;
+
+    for sign in [1, -1] {
+        let valid = |from: usize, to: usize| {
+            let step = (report[to] - report[from]) * sign;
+            step >= dampener.min_step && step <= dampener.max_step
+        };
+
+        let mut removals = vec![usize::MAX; n];
+        let mut previous = vec![None; n];
+        let mut end = None;
+        for i in 0..n {
+            if i <= k {
+                removals[i] = i;
+            }
+            for j in i.saturating_sub(k + 1)..i {
+                let count = removals[j].saturating_add(i - j - 1);
+                if removals[j] != usize::MAX && count <= removals[i] && valid(j, i) {
+                    removals[i] = count;
+                    previous[i] = Some(j);
                 }
             }
-            false
+            let total = removals[i].saturating_add(n - 1 - i);
+            if total <= k && end.is_none_or(|e: (usize, usize)| total < e.1) {
+                end = Some((i, total));
+            }
+        }
+
+        if let Some((mut i, _)) = end {
+            let mut kept = vec![false; n];
+            kept[i] = true;
+            while let Some(j) = previous[i] {
+                kept[j] = true;
+                i = j;
+            }
+            let removed = (0..n).filter(|&i| !kept[i]).collect::<Vec<_>>();
+            if best.as_ref().is_none_or(|b| removed.len() < b.len()) {
+                best = Some(removed);
+            }
         }
     }
+
+    match best {
+        Some(removed) => Check::Safe { removed },
+        None if n < 2 => Check::Safe { removed: vec![] },
+        None => first_violation(report, dampener),
+    }
 }
 
-fn part2(input: &str) -> i32 {
-    let mut acc = 0;
-    for line in input.lines() {
-        let report = line
-            .split_whitespace()
-            .filter_map(|s| s.parse::<i32>().ok())
-            .collect::<Vec<i32>>();
-        if is_safe_with_tolerance(&report) {
-            acc += 1;
-        }
+/// The first pair that breaks the rules, with the direction set by the first two levels
+fn first_violation(report: &[i32], dampener: &Dampener) -> Check {
+    let sign = (report[1] - report[0]).signum();
+    for (i, w) in report.windows(2).enumerate() {
+        let diff = w[1] - w[0];
+        let violation = if diff == 0 && dampener.min_step > 0 {
+            Violation::Unchanged
+        } else if diff.signum() != sign && diff != 0 {
+            Violation::DirectionChanged
+        } else if diff.abs() < dampener.min_step {
+            Violation::StepTooSmall(diff)
+        } else if diff.abs() > dampener.max_step {
+            Violation::StepTooLarge(diff)
+        } else {
+            continue;
+        };
+        return Check::Unsafe {
+            index: i + 1,
+            violation,
+        };
     }
-    acc
+    // Every pair is fine, so it was the number of removals that was not enough
+    unreachable!("Report {report:?} is safe");
+}
+
+fn is_safe(report: &[i32]) -> bool {
+    check(report, &PART1).is_safe()
+}
+
+fn part1(reports: &[Vec<i32>]) -> i32 {
+    reports.iter().filter(|r| is_safe(r)).count() as i32
+}
+
+fn is_safe_with_tolerance(report: &[i32]) -> bool {
+    check(report, &PART2).is_safe()
+}
+
+fn part2(reports: &[Vec<i32>]) -> i32 {
+    reports.iter().filter(|r| is_safe_with_tolerance(r)).count() as i32
 }
 
 #[cfg(test)]
@@ -90,28 +210,111 @@ mod tests {
         let descending = [7, 6, 4, 2, 1];
         let ascending = [1, 3, 6, 7, 9];
         let not_safe = [1, 2, 7, 8, 9];
-        assert_eq!(is_safe(&ascending), true);
-        assert_eq!(is_safe(&descending), true);
-        assert_eq!(is_safe(&not_safe), false);
+        assert!(is_safe(&ascending));
+        assert!(is_safe(&descending));
+        assert!(!is_safe(&not_safe));
     }
 
     #[test]
     fn test_part1() {
-        assert_eq!(part1(INPUT), 2);
+        assert_eq!(part1(&parse_input(INPUT).unwrap()), 2);
+        assert_eq!(part1(&parse_input("7 6 4 2 1\n\n1 2 7 8 9\n").unwrap()), 1);
     }
 
     #[test]
     fn test_is_safe_with_tolerance() {
-        assert_eq!(is_safe_with_tolerance(&[7, 6, 4, 2, 1]), true);
-        assert_eq!(is_safe_with_tolerance(&[1, 2, 7, 8, 9]), false);
-        assert_eq!(is_safe_with_tolerance(&[9, 7, 6, 2, 1]), false);
-        assert_eq!(is_safe_with_tolerance(&[1, 3, 2, 4, 5]), true);
-        assert_eq!(is_safe_with_tolerance(&[8, 6, 4, 4, 1]), true);
-        assert_eq!(is_safe_with_tolerance(&[1, 3, 6, 7, 9]), true);
+        assert!(is_safe_with_tolerance(&[7, 6, 4, 2, 1]));
+        assert!(!is_safe_with_tolerance(&[1, 2, 7, 8, 9]));
+        assert!(!is_safe_with_tolerance(&[9, 7, 6, 2, 1]));
+        assert!(is_safe_with_tolerance(&[1, 3, 2, 4, 5]));
+        assert!(is_safe_with_tolerance(&[8, 6, 4, 4, 1]));
+        assert!(is_safe_with_tolerance(&[1, 3, 6, 7, 9]));
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(
+            check(&[1, 3, 2, 4, 5], &PART2),
+            Check::Safe { removed: vec![1] }
+        );
+        assert_eq!(
+            check(&[8, 6, 4, 4, 1], &PART2),
+            Check::Safe { removed: vec![2] }
+        );
+        assert_eq!(
+            check(&[1, 2, 7, 8, 9], &PART2),
+            Check::Unsafe {
+                index: 2,
+                violation: Violation::StepTooLarge(5)
+            }
+        );
+        assert_eq!(
+            check(&[9, 7, 6, 2, 1], &PART2).to_string(),
+            "Unsafe, level 3 changes too much (-4)"
+        );
+        assert_eq!(
+            check(&[1, 3, 2, 4, 5], &PART1).to_string(),
+            "Unsafe, level 2 changes direction"
+        );
+        assert_eq!(
+            check(&[8, 6, 4, 4, 1], &PART1).to_string(),
+            "Unsafe, level 3 is unchanged"
+        );
+        // Removing the first level is also allowed
+        assert_eq!(
+            check(&[5, 1, 2, 3], &PART2),
+            Check::Safe { removed: vec![0] }
+        );
+        assert_eq!(check(&[5], &PART1), Check::Safe { removed: vec![] });
+    }
+
+    #[test]
+    fn test_dampener() {
+        let two = Dampener {
+            max_removals: 2,
+            ..PART1
+        };
+        assert_eq!(
+            check(&[1, 9, 2, 9, 3], &two),
+            Check::Safe {
+                removed: vec![1, 3]
+            }
+        );
+        assert!(!check(&[1, 9, 2, 9, 3, 9], &two).is_safe());
+        let wide = Dampener {
+            max_step: 5,
+            ..PART1
+        };
+        assert!(check(&[1, 2, 7, 8, 9], &wide).is_safe());
+        let any = Dampener {
+            max_removals: usize::MAX,
+            ..PART1
+        };
+        assert!(check(&[1, 9, 2, 9, 3, 9], &any).is_safe());
+        let strict = Dampener {
+            min_step: 2,
+            ..PART1
+        };
+        assert_eq!(
+            check(&[1, 3, 4], &strict),
+            Check::Unsafe {
+                index: 2,
+                violation: Violation::StepTooSmall(1)
+            }
+        );
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(INPUT), 4);
+        assert_eq!(part2(&parse_input(INPUT).unwrap()), 4);
+        assert_eq!(part2(&parse_input("1 3 2 4 5\n  \n").unwrap()), 1);
+    }
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(
+            parse_input("7 6 4 2 1\n1 2 x 8 9").unwrap_err().to_string(),
+            "line 2: invalid value 'x'"
+        );
     }
 }
@@ -1,53 +1,200 @@
+use std::ops::Range;
+
 use regex::Regex;
 
 pub fn main() {
     let input = std::fs::read_to_string("input/day3.txt").expect("No input");
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    match std::env::args().nth(2).as_deref() {
+        Some("trace") => {
+            let (_, events) = Interpreter::part2().run(&input);
+            println!("{}", format_trace(&events));
+        }
+        Some("dry-run") => {
+            for call in Interpreter::part2().scan(&input) {
+                println!("{:?} {}", call.span, call);
+            }
+        }
+        _ => {
+            println!("Part 1: {}", part1(&input));
+            println!("Part 2: {}", part2(&input));
+        }
+    }
 }
 
-fn part1(input: &str) -> i32 {
-    let re = Regex::new("mul\\((\\d{1,3}),(\\d{1,3})\\)").unwrap();
-    let mut acc = 0;
-    for m in re.captures_iter(input) {
-        let lhs: i32 = m
-            .get(1)
-            .and_then(|s| s.as_str().parse::<i32>().ok())
-            .unwrap();
-        let rhs: i32 = m
-            .get(2)
-            .and_then(|s| s.as_str().parse::<i32>().ok())
-            .unwrap();
-        acc += lhs * rhs;
-    }
-    acc
+#[derive(Debug, PartialEq, Eq)]
+struct State {
+    enabled: bool,
+    acc: i32,
 }
 
-fn part2(input: &str) -> i32 {
-    let re = Regex::new("mul\\((\\d{1,3}),(\\d{1,3})\\)|do\\(\\)|don\\'t\\(\\)").unwrap();
-    let mut acc = 0;
-    let mut enabled = true;
-    for m in re.captures_iter(input) {
-        match m.get(0).and_then(|m| Some(m.as_str())) {
-            None => unimplemented!(),
-            Some("do()") => enabled = true,
-            Some("don't()") => enabled = false,
-            _ => {
-                if enabled {
-                    let lhs: i32 = m
-                        .get(1)
-                        .and_then(|s| s.as_str().parse::<i32>().ok())
-                        .unwrap();
-                    let rhs: i32 = m
-                        .get(2)
-                        .and_then(|s| s.as_str().parse::<i32>().ok())
-                        .unwrap();
-                    acc += lhs * rhs;
-                }
+/// An instruction in the corrupted memory, written as `name(arg,...)` with up to three digits
+/// per argument.
+struct Instruction {
+    name: &'static str,
+    arity: usize,
+    /// Conditional instructions are skipped while the state is disabled
+    conditional: bool,
+    execute: fn(&mut State, &[i32]),
+}
+
+fn mul() -> Instruction {
+    Instruction {
+        name: "mul",
+        arity: 2,
+        conditional: true,
+        execute: |state, args| state.acc += args[0] * args[1],
+    }
+}
+
+fn enable() -> Instruction {
+    Instruction {
+        name: "do",
+        arity: 0,
+        conditional: false,
+        execute: |state, _| state.enabled = true,
+    }
+}
+
+fn disable() -> Instruction {
+    Instruction {
+        name: "don't",
+        arity: 0,
+        conditional: false,
+        execute: |state, _| state.enabled = false,
+    }
+}
+
+/// A well formed instruction found in the memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Call {
+    /// Index into the registered instructions
+    instruction: usize,
+    name: &'static str,
+    args: Vec<i32>,
+    /// Byte range in the memory
+    span: Range<usize>,
+}
+impl std::fmt::Display for Call {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args = self
+            .args
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(f, "{}({})", self.name, args)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    call: Call,
+    executed: bool,
+}
+
+struct Interpreter {
+    instructions: Vec<Instruction>,
+}
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            instructions: vec![],
+        }
+    }
+
+    pub fn part1() -> Self {
+        let mut interpreter = Self::new();
+        interpreter.register(mul());
+        interpreter
+    }
+
+    pub fn part2() -> Self {
+        let mut interpreter = Self::part1();
+        interpreter.register(enable());
+        interpreter.register(disable());
+        interpreter
+    }
+
+    pub fn register(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+
+    /// Finds every well formed call to a registered instruction, in order. Anything else in the
+    /// memory is ignored.
+    pub fn scan(&self, input: &str) -> Vec<Call> {
+        if self.instructions.is_empty() {
+            return vec![];
+        }
+        let names = self
+            .instructions
+            .iter()
+            .map(|i| regex::escape(i.name))
+            .collect::<Vec<_>>()
+            .join("|");
+        let re = Regex::new(&format!("({names})\\(((?:\\d{{1,3}}(?:,\\d{{1,3}})*)?)\\)")).unwrap();
+
+        let mut calls = Vec::new();
+        for m in re.captures_iter(input) {
+            let (Some(full), Some(name), Some(args)) = (m.get(0), m.get(1), m.get(2)) else {
+                continue;
+            };
+            let args = args
+                .as_str()
+                .split(',')
+                .filter_map(|s| s.parse::<i32>().ok())
+                .collect::<Vec<_>>();
+            let instruction = self
+                .instructions
+                .iter()
+                .position(|i| i.name == name.as_str() && i.arity == args.len());
+            if let Some(instruction) = instruction {
+                calls.push(Call {
+                    instruction,
+                    name: self.instructions[instruction].name,
+                    args,
+                    span: full.range(),
+                });
             }
         }
+        calls
+    }
+
+    /// Executes the memory and reports every instruction found and whether it was executed
+    pub fn run(&self, input: &str) -> (State, Vec<Event>) {
+        let mut state = State {
+            enabled: true,
+            acc: 0,
+        };
+        let mut events = Vec::new();
+        for call in self.scan(input) {
+            let instruction = &self.instructions[call.instruction];
+            let executed = state.enabled || !instruction.conditional;
+            if executed {
+                (instruction.execute)(&mut state, &call.args);
+            }
+            events.push(Event { call, executed });
+        }
+        (state, events)
     }
-    acc
+}
+
+fn format_trace(events: &[Event]) -> String {
+    events
+        .iter()
+        .map(|e| {
+            let status = if e.executed { "executed" } else { "skipped" };
+            format!("{:?} {} {status}", e.call.span, e.call)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn part1(input: &str) -> i32 {
+    Interpreter::part1().run(input).0.acc
+}
+
+fn part2(input: &str) -> i32 {
+    Interpreter::part2().run(input).0.acc
 }
 
 #[cfg(test)]
@@ -66,4 +213,54 @@ mod tests {
     fn test_part2() {
         assert_eq!(part2(INPUT2), 48);
     }
+
+    #[test]
+    fn test_scan() {
+        let calls = Interpreter::part1().scan(INPUT);
+        assert_eq!(
+            calls.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            vec!["mul(2,4)", "mul(5,5)", "mul(11,8)", "mul(8,5)"]
+        );
+        assert_eq!(calls[0].span, 1..9);
+        assert_eq!(&INPUT[calls[2].span.clone()], "mul(11,8)");
+        assert!(Interpreter::part1()
+            .scan("mul(1,2,3)mul(1234,1)mul()")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_trace() {
+        let (state, events) = Interpreter::part2().run(INPUT2);
+        assert!(state.enabled);
+        assert_eq!(
+            format_trace(&events),
+            "1..9 mul(2,4) executed
+20..27 don't() executed
+28..36 mul(5,5) skipped
+48..57 mul(11,8) skipped
+59..63 do() executed
+64..72 mul(8,5) executed"
+        );
+    }
+
+    #[test]
+    fn test_register() {
+        let mut interpreter = Interpreter::part2();
+        interpreter.register(Instruction {
+            name: "add",
+            arity: 3,
+            conditional: true,
+            execute: |state, args| state.acc += args.iter().sum::<i32>(),
+        });
+        interpreter.register(Instruction {
+            name: "reset",
+            arity: 0,
+            conditional: false,
+            execute: |state, _| state.acc = 0,
+        });
+        let (state, _) = interpreter.run("mul(2,3)add(1,2,3)xadd(1,2)don't()add(4,5,6)");
+        assert_eq!(state.acc, 12);
+        let (state, _) = interpreter.run("mul(2,3)reset()mul(1,2)");
+        assert_eq!(state.acc, 2);
+    }
 }
@@ -1,3 +1,4 @@
+use crossterm::style::Stylize;
 use glam::IVec2;
 
 pub fn main() {
@@ -6,6 +7,28 @@ pub fn main() {
     let (rows, cols) = get_rows_cols(&input);
     let input = input.lines().collect::<Vec<_>>().join("");
 
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("show") {
+        // `cargo run 4 show 2 color` highlights the matches of part 2 in colour
+        let grid = Grid::new(&input, rows, cols);
+        let positions = match args.get(3).map(String::as_str) {
+            Some("2") => {
+                let patterns = x_mas();
+                find_patterns(&grid, &patterns)
+                    .iter()
+                    .flat_map(|m| m.positions(&patterns))
+                    .collect::<Vec<_>>()
+            }
+            _ => find_words(&grid, &["XMAS"])
+                .iter()
+                .flat_map(|m| m.positions())
+                .collect(),
+        };
+        let color = args.get(4).map(String::as_str) == Some("color");
+        println!("{}", render_matches(&grid, &positions, color));
+        return;
+    }
+
     println!("Part 1: {}", part1(&input, rows, cols));
     println!("Part 2: {}", part2(&input, rows, cols));
 }
@@ -14,32 +37,43 @@ fn pos_to_idx(pos: IVec2, rows: usize, cols: usize) -> Option<usize> {
     if pos.x >= cols as i32 || pos.y >= rows as i32 || pos.x < 0 || pos.y < 0 {
         return None;
     }
-    Some((pos.y * (rows as i32) + pos.x) as usize)
+    Some((pos.y * (cols as i32) + pos.x) as usize)
 }
 
 fn idx_to_pos(i: usize, rows: usize, cols: usize) -> Option<IVec2> {
     if i >= rows * cols {
         return None;
     }
-    let x = i % rows;
-    let y = i / rows;
+    let x = i % cols;
+    let y = i / cols;
     Some(IVec2 {
         x: x as i32,
         y: y as i32,
     })
 }
 
-fn get_word(input: &str, positions: &[IVec2], rows: usize, cols: usize) -> String {
-    let word = positions
-        .iter()
-        .filter_map(|p: &IVec2| {
-            if let Some(idx) = pos_to_idx(*p, rows, cols) {
-                return input.chars().nth(idx);
-            }
-            None
-        })
-        .collect::<String>();
-    return word;
+struct Grid {
+    rows: usize,
+    cols: usize,
+    letters: Vec<char>,
+}
+impl Grid {
+    /// `input` is the puzzle with all linebreaks removed
+    fn new(input: &str, rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            letters: input.chars().collect(),
+        }
+    }
+
+    fn get(&self, pos: IVec2) -> Option<char> {
+        pos_to_idx(pos, self.rows, self.cols).map(|i| self.letters[i])
+    }
+
+    fn positions(&self) -> impl Iterator<Item = IVec2> + '_ {
+        (0..self.letters.len()).filter_map(|i| idx_to_pos(i, self.rows, self.cols))
+    }
 }
 
 fn create_neighbour_matrix(count: usize) -> Vec<Vec<IVec2>> {
@@ -87,100 +121,212 @@ fn create_neighbour_matrix(count: usize) -> Vec<Vec<IVec2>> {
     neighbours
 }
 
-fn center_on_index(pos: IVec2, neighbours: &Vec<Vec<IVec2>>) -> Vec<Vec<IVec2>> {
-    let mut neighbours = neighbours.clone();
+fn center_on_index(pos: IVec2, neighbours: &[Vec<IVec2>]) -> Vec<Vec<IVec2>> {
+    let mut neighbours = neighbours.to_vec();
     for n in neighbours.iter_mut().flatten() {
         *n += pos;
     }
     neighbours
 }
 
-fn find_words_starting_from(
-    input: &str,
-    neighbours: &Vec<Vec<IVec2>>,
-    idx: usize,
-    word: &str,
-    rows: usize,
-    cols: usize,
-) -> i32 {
-    let Some(pos) = idx_to_pos(idx, rows, cols) else {
-        return 0;
-    };
-    let neighbours = center_on_index(pos, neighbours);
-    neighbours.iter().fold(0, |acc, positions| {
-        if &get_word(input, positions, rows, cols) == word {
-            return acc + 1;
-        }
-        acc
-    })
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WordMatch {
+    word: String,
+    start: IVec2,
+    /// One of the eight unit steps
+    dir: IVec2,
 }
-
-fn get_rows_cols(input: &str) -> (usize, usize) {
-    let rows = input
-        .lines()
-        .nth(0)
-        .expect("Received empty input")
-        .chars()
-        .count();
-    let cols = input.lines().count();
-    (rows, cols)
+impl WordMatch {
+    fn positions(&self) -> Vec<IVec2> {
+        (0..self.word.chars().count())
+            .map(|i| self.start + self.dir * i as i32)
+            .collect()
+    }
 }
 
-fn part1(input: &str, rows: usize, cols: usize) -> i32 {
-    let neighbours = create_neighbour_matrix(3);
-    let mut acc = 0;
-    for (idx, c) in input.chars().enumerate() {
-        if c == 'X' {
-            acc += find_words_starting_from(&input, &neighbours, idx, "MAS", rows, cols);
+/// Finds every occurrence of every word, in all eight directions
+fn find_words(grid: &Grid, words: &[&str]) -> Vec<WordMatch> {
+    let mut matches = Vec::new();
+    for word in words {
+        let mut letters = word.chars();
+        let Some(first) = letters.next() else {
+            continue;
+        };
+        let rest = letters.collect::<String>();
+        if rest.is_empty() {
+            // A single letter has no direction
+            for pos in grid.positions().filter(|&p| grid.get(p) == Some(first)) {
+                matches.push(WordMatch {
+                    word: word.to_string(),
+                    start: pos,
+                    dir: IVec2::ZERO,
+                });
+            }
+            continue;
+        }
+
+        let neighbours = create_neighbour_matrix(rest.chars().count());
+        for pos in grid.positions().filter(|&p| grid.get(p) == Some(first)) {
+            for positions in center_on_index(pos, &neighbours) {
+                let found = positions
+                    .iter()
+                    .map(|&p| grid.get(p))
+                    .collect::<Option<String>>();
+                if found.as_deref() == Some(rest.as_str()) {
+                    matches.push(WordMatch {
+                        word: word.to_string(),
+                        start: pos,
+                        dir: positions[0] - pos,
+                    });
+                }
+            }
         }
     }
-    acc
+    matches
 }
 
-fn find_cross(input: &str, idx: usize, rows: usize, cols: usize) -> bool {
-    let Some(pos) = idx_to_pos(idx, rows, cols) else {
-        return false;
-    };
+/// A 2D mask of letters relative to its top left corner. Positions without a letter match
+/// anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    cells: Vec<(IVec2, char)>,
+}
+impl Pattern {
+    /// Every character except `.` has to match, e.g. `"M.S\n.A.\nM.S"`
+    fn parse(mask: &str) -> Self {
+        let mut cells = Vec::new();
+        for (y, l) in mask.lines().enumerate() {
+            for (x, c) in l.chars().enumerate() {
+                if c != '.' {
+                    cells.push((IVec2::new(x as i32, y as i32), c));
+                }
+            }
+        }
+        Self::normalized(cells)
+    }
 
-    let Some(upper_left) = get_char(pos + IVec2::new(-1, -1), rows, cols, input) else {
-        return false;
-    };
-    let Some(upper_right) = get_char(pos + IVec2::new(1, -1), rows, cols, input) else {
-        return false;
-    };
-    let Some(lower_right) = get_char(pos + IVec2::new(1, 1), rows, cols, input) else {
-        return false;
-    };
-    let Some(lower_left) = get_char(pos + IVec2::new(-1, 1), rows, cols, input) else {
-        return false;
-    };
+    /// Moves the pattern so it starts at zero and sorts the cells, so equal patterns compare equal
+    fn normalized(mut cells: Vec<(IVec2, char)>) -> Self {
+        let min = cells
+            .iter()
+            .map(|(p, _)| *p)
+            .reduce(IVec2::min)
+            .unwrap_or(IVec2::ZERO);
+        for (p, _) in cells.iter_mut() {
+            *p -= min;
+        }
+        cells.sort_by_key(|(p, _)| (p.y, p.x));
+        Self { cells }
+    }
 
-    let mut acc = 0;
-    if (upper_left == 'S' && lower_right == 'M') || (upper_left == 'M' && lower_right == 'S') {
-        acc += 1;
+    /// The pattern turned a quarter clockwise
+    fn rotated(&self) -> Self {
+        Self::normalized(
+            self.cells
+                .iter()
+                .map(|&(p, c)| (IVec2::new(-p.y, p.x), c))
+                .collect(),
+        )
     }
-    if (upper_right == 'S' && lower_left == 'M') || (upper_right == 'M' && lower_left == 'S') {
-        acc += 1;
+
+    /// All distinct rotations of the pattern
+    fn rotations(&self) -> Vec<Pattern> {
+        let mut rotations = vec![self.clone()];
+        for _ in 0..3 {
+            let next = rotations.last().unwrap().rotated();
+            if !rotations.contains(&next) {
+                rotations.push(next);
+            }
+        }
+        rotations
+    }
+
+    fn matches_at(&self, grid: &Grid, pos: IVec2) -> bool {
+        self.cells
+            .iter()
+            .all(|&(p, c)| grid.get(pos + p) == Some(c))
     }
-    return acc == 2;
 }
 
-fn get_char(pos: IVec2, rows: usize, cols: usize, input: &str) -> Option<char> {
-    if let Some(lower_left) = pos_to_idx(pos, rows, cols) {
-        return input.chars().nth(lower_left);
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PatternMatch {
+    pattern: usize,
+    pos: IVec2,
+}
+impl PatternMatch {
+    /// `patterns` must be the same as the match was found with
+    fn positions(&self, patterns: &[Pattern]) -> Vec<IVec2> {
+        patterns[self.pattern]
+            .cells
+            .iter()
+            .map(|(p, _)| self.pos + *p)
+            .collect()
     }
-    None
 }
 
-fn part2(input: &str, rows: usize, cols: usize) -> i32 {
-    input.chars().enumerate().fold(0, |acc, (idx, c)| {
-        if c == 'A' {
-            if find_cross(input, idx, rows, cols) {
-                return acc + 1;
+/// Finds every position where one of the patterns matches. `pattern` in the result is the
+/// index into `patterns`.
+fn find_patterns(grid: &Grid, patterns: &[Pattern]) -> Vec<PatternMatch> {
+    let mut matches = Vec::new();
+    for pos in grid.positions() {
+        for (i, pattern) in patterns.iter().enumerate() {
+            if pattern.matches_at(grid, pos) {
+                matches.push(PatternMatch { pattern: i, pos });
             }
         }
-        acc
-    })
+    }
+    matches
+}
+
+/// Draws the grid with every letter that is not part of a match replaced by `.`, like the
+/// puzzle does. With `color` set all letters are kept and the matches are highlighted instead.
+fn render_matches(grid: &Grid, positions: &[IVec2], color: bool) -> String {
+    let mut highlighted = vec![false; grid.letters.len()];
+    for &p in positions {
+        if let Some(idx) = pos_to_idx(p, grid.rows, grid.cols) {
+            highlighted[idx] = true;
+        }
+    }
+
+    grid.letters
+        .iter()
+        .zip(highlighted)
+        .map(|(&c, h)| match (h, color) {
+            (true, true) => c.red().bold().to_string(),
+            (false, true) => c.dark_grey().to_string(),
+            (true, false) => c.to_string(),
+            (false, false) => ".".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .chunks(grid.cols)
+        .map(|row| row.concat())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn get_rows_cols(input: &str) -> (usize, usize) {
+    let cols = input
+        .lines()
+        .next()
+        .expect("Received empty input")
+        .chars()
+        .count();
+    let rows = input.lines().count();
+    (rows, cols)
+}
+
+fn part1(input: &str, rows: usize, cols: usize) -> i32 {
+    let grid = Grid::new(input, rows, cols);
+    find_words(&grid, &["XMAS"]).len() as i32
+}
+
+fn x_mas() -> Vec<Pattern> {
+    Pattern::parse("M.S\n.A.\nM.S").rotations()
+}
+
+fn part2(input: &str, rows: usize, cols: usize) -> i32 {
+    let grid = Grid::new(input, rows, cols);
+    find_patterns(&grid, &x_mas()).len() as i32
 }
 
 #[cfg(test)]
@@ -207,6 +353,113 @@ mod tests {
         assert_eq!(part1(INPUT, 10, 10), 18);
     }
 
+    #[test]
+    fn test_find_words() {
+        let grid = Grid::new("XMASAMXSAMXA", 2, 6);
+        let matches = find_words(&grid, &["XMAS", "SAM", "Q"]);
+        assert_eq!(
+            matches,
+            vec![
+                WordMatch {
+                    word: "XMAS".to_string(),
+                    start: IVec2::new(0, 0),
+                    dir: IVec2::X
+                },
+                WordMatch {
+                    word: "XMAS".to_string(),
+                    start: IVec2::new(4, 1),
+                    dir: IVec2::NEG_X
+                },
+                WordMatch {
+                    word: "SAM".to_string(),
+                    start: IVec2::new(3, 0),
+                    dir: IVec2::X
+                },
+                WordMatch {
+                    word: "SAM".to_string(),
+                    start: IVec2::new(3, 0),
+                    dir: IVec2::NEG_X
+                },
+                WordMatch {
+                    word: "SAM".to_string(),
+                    start: IVec2::new(1, 1),
+                    dir: IVec2::X
+                },
+            ]
+        );
+        assert_eq!(
+            matches[0].positions(),
+            vec![
+                IVec2::new(0, 0),
+                IVec2::new(1, 0),
+                IVec2::new(2, 0),
+                IVec2::new(3, 0)
+            ]
+        );
+        assert_eq!(find_words(&grid, &["A"]).len(), 4);
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(x_mas().len(), 4);
+        let plus = Pattern::parse(".M.\nMAS\n.S.");
+        assert_eq!(plus.rotations().len(), 4);
+        let symmetric = Pattern::parse("A.A\n.A.\nA.A");
+        assert_eq!(symmetric.rotations().len(), 1);
+
+        let grid = Grid::new("SXMMAXSSM", 3, 3);
+        assert_eq!(
+            find_patterns(&grid, &x_mas()),
+            vec![PatternMatch {
+                pattern: 2,
+                pos: IVec2::ZERO
+            }]
+        );
+        let grid = Grid::new("M.SMASS.S", 3, 3);
+        assert_eq!(find_patterns(&grid, &plus.rotations()).len(), 0);
+    }
+
+    #[test]
+    fn test_render_matches() {
+        let grid = Grid::new(INPUT, 10, 10);
+        let positions = find_words(&grid, &["XMAS"])
+            .iter()
+            .flat_map(|m| m.positions())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            render_matches(&grid, &positions, false),
+            "....XXMAS.
+.SAMXMS...
+...S..A...
+..A.A.MS.X
+XMASAMX.MM
+X.....XA.A
+S.S.S.S.SS
+.A.A.A.A.A
+..M.M.M.MM
+.X.X.XMASX"
+        );
+
+        let patterns = x_mas();
+        let positions = find_patterns(&grid, &patterns)
+            .iter()
+            .flat_map(|m| m.positions(&patterns))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            render_matches(&grid, &positions, false),
+            ".M.S......
+..A..MSMS.
+.M.S.MAA..
+..A.ASMSM.
+.M.S.M....
+..........
+S.S.S.S.S.
+.A.A.A.A..
+M.M.M.M.M.
+.........."
+        );
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(INPUT, 10, 10), 9);
@@ -1,5 +1,19 @@
 pub fn main() {
     let input = std::fs::read_to_string("input/day5.txt").expect("No input");
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("dot") {
+        // Optionally restrict the graph to a single update, e.g. `cargo run 5 dot 3`
+        let reqs = parse_requirements(&input);
+        let updates = parse_updates(&input);
+        let pages = args
+            .get(3)
+            .and_then(|s| s.parse::<usize>().ok())
+            .and_then(|i| updates.get(i));
+        let dot = to_dot(&reqs, pages.map(|p| p.as_slice()));
+        std::fs::write("day5.dot", dot).expect("Could not write day5.dot");
+        println!("Wrote day5.dot");
+        return;
+    }
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
 }
@@ -39,20 +53,22 @@ fn check_req(page: i32, pages: &[i32], reqs: &[Requirement]) -> bool {
     true
 }
 
+fn parse_updates(input: &str) -> Vec<Vec<i32>> {
+    input
+        .lines()
+        .filter(|l| !l.contains('|') && !l.is_empty())
+        .map(|l| {
+            l.split(',')
+                .filter_map(|s| s.parse::<i32>().ok())
+                .collect::<Vec<i32>>()
+        })
+        .collect()
+}
+
 fn get_sorted_unsorted(input: &str, reqs: &[Requirement]) -> (Vec<Vec<i32>>, Vec<Vec<i32>>) {
     let mut sorted = Vec::new();
     let mut unsorted = Vec::new();
-    'outer: for l in input.lines() {
-        if l.contains('|') {
-            // Skip all requirements
-            continue;
-        }
-
-        let pages = l
-            .split(',')
-            .filter_map(|s| s.parse::<i32>().ok())
-            .collect::<Vec<i32>>();
-
+    'outer: for pages in parse_updates(input) {
         for (i, p) in pages.iter().enumerate() {
             if !check_req(*p, &pages[0..i], reqs) {
                 unsorted.push(pages);
@@ -130,6 +146,42 @@ fn part2(input: &str) -> i32 {
     acc
 }
 
+/// Returns true if `req` is broken by the order of `pages`, i.e. `before` is printed first.
+fn is_violated(req: &Requirement, pages: &[i32]) -> bool {
+    let value = pages.iter().position(|&p| p == req.value);
+    let before = pages.iter().position(|&p| p == req.before);
+    matches!((value, before), (Some(v), Some(b)) if b < v)
+}
+
+/// Writes the requirements as a Graphviz digraph with an edge `value -> before` for each rule.
+/// If `pages` is given, only rules between those pages are included and the edges that the
+/// update violates are drawn in red.
+fn to_dot(reqs: &[Requirement], pages: Option<&[i32]>) -> String {
+    let mut out = String::from("digraph day5 {\n");
+    if let Some(pages) = pages {
+        for p in pages {
+            out += &format!("    {p};\n");
+        }
+    }
+    for r in reqs {
+        match pages {
+            None => out += &format!("    {} -> {};\n", r.value, r.before),
+            Some(pages) => {
+                if !pages.contains(&r.value) || !pages.contains(&r.before) {
+                    continue;
+                }
+                if is_violated(r, pages) {
+                    out += &format!("    {} -> {} [color=red, penwidth=2];\n", r.value, r.before);
+                } else {
+                    out += &format!("    {} -> {};\n", r.value, r.before);
+                }
+            }
+        }
+    }
+    out += "}\n";
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,6 +211,19 @@ mod tests {
         assert_eq!(sort(&[97, 13, 75, 29, 47], &reqs), vec![97, 75, 47, 29, 13]);
     }
 
+    #[test]
+    fn test_to_dot() {
+        let reqs = parse_requirements("2|3\n1|2\n3|4");
+        assert_eq!(
+            to_dot(&reqs, None),
+            "digraph day5 {\n    2 -> 3;\n    1 -> 2;\n    3 -> 4;\n}\n"
+        );
+        assert_eq!(
+            to_dot(&reqs, Some(&[3, 2])),
+            "digraph day5 {\n    3;\n    2;\n    2 -> 3 [color=red, penwidth=2];\n}\n"
+        );
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(INPUT), 123);
@@ -1,8 +1,48 @@
+use std::{io, time::Duration};
+
 use glam::IVec2;
-use itertools::Itertools;
+use ratatui::{
+    crossterm::{
+        event::{self, KeyCode, KeyEventKind},
+        style::Color,
+    },
+    layout::{Constraint, Layout},
+    widgets::Paragraph,
+};
+use rayon::prelude::*;
+
+use crate::export;
+use crate::render::{Frame, Highlight};
 
 pub fn main() {
     let input = std::fs::read_to_string("input/day6.txt").expect("No input");
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(2).map(String::as_str) {
+        Some("animate") => {
+            let _ = animate(&input);
+            return;
+        }
+        Some("map") => {
+            let (_, visited) = part1(&input);
+            let mut world = parse_world(&input);
+            while step(&mut world) != Action::Done {}
+            println!(
+                "{}",
+                create_sprite(&world, &loop_obstacles(&input, &visited)).to_text()
+            );
+            return;
+        }
+        Some("export") => {
+            // `cargo run 6 export out=day6.gif every=5 cell=6`
+            let defaults = export::Options {
+                every: 10,
+                ..export::Options::new("day6.gif")
+            };
+            export::run(&args[3..], defaults, patrol_frames(&input));
+            return;
+        }
+        _ => {}
+    }
     let (count, visited) = part1(&input);
     println!("Part 1: {count}",);
     println!("Part 2: {}", part2(&input, &visited));
@@ -15,34 +55,87 @@ enum Direction {
     South,
     West,
 }
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::East,
+    Direction::South,
+    Direction::West,
+];
 
+/// Where the guard ends up when walking straight from a tile until something is in the way.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-struct Visited {
-    pub tile: IVec2,
-    pub dir: Direction,
+enum Jump {
+    /// The last free tile before an obstacle
+    Stop(IVec2),
+    /// The guard walks off the map
+    Exit,
 }
 
 #[derive(Debug, Clone)]
 struct World {
-    pub obstacles: Vec<IVec2>,
+    /// One flag per tile, indexed with `pos_to_idx`
+    pub obstacles: Vec<bool>,
+    pub guard_start: IVec2,
     pub guard_pos: IVec2,
     pub guard_facing: Direction,
-    pub visited: Vec<Visited>,
+    /// One bit per direction for every tile, see `dir_bit`
+    pub visited: Vec<u8>,
+    /// Precomputed straight walks for every tile and direction
+    pub jumps: Vec<[Jump; 4]>,
     pub dimension: IVec2,
 }
 impl World {
-    pub fn new(obstacles: Vec<IVec2>, guard_pos: IVec2, dimension: IVec2) -> Self {
-        World {
+    pub fn new(obstacles: Vec<bool>, guard_pos: IVec2, dimension: IVec2) -> Self {
+        let jumps = create_jumps(&obstacles, dimension);
+        let mut world = World {
+            visited: vec![0; obstacles.len()],
             obstacles,
+            guard_start: guard_pos,
             guard_pos,
             guard_facing: Direction::North,
-            visited: vec![Visited {
-                tile: guard_pos,
-                dir: Direction::North,
-            }],
+            jumps,
             dimension,
+        };
+        world.visit(guard_pos, Direction::North);
+        world
+    }
+
+    fn idx(&self, pos: IVec2) -> Option<usize> {
+        pos_to_idx(pos, self.dimension)
+    }
+
+    fn is_obstacle(&self, pos: IVec2) -> bool {
+        self.idx(pos).is_some_and(|i| self.obstacles[i])
+    }
+
+    fn visit(&mut self, pos: IVec2, dir: Direction) {
+        if let Some(i) = self.idx(pos) {
+            self.visited[i] |= dir_bit(dir);
         }
     }
+
+    /// All tiles the guard has been on, in row major order
+    pub fn visited_tiles(&self) -> Vec<IVec2> {
+        self.visited
+            .iter()
+            .enumerate()
+            .filter(|(_, &v)| v != 0)
+            .map(|(i, _)| idx_to_pos(i, self.dimension))
+            .collect()
+    }
+}
+
+/// `dimension` is the largest valid coordinate, not the size of the map
+fn pos_to_idx(pos: IVec2, dimension: IVec2) -> Option<usize> {
+    if pos.x > dimension.x || pos.y > dimension.y || pos.x < 0 || pos.y < 0 {
+        return None;
+    }
+    Some((pos.y * (dimension.x + 1) + pos.x) as usize)
+}
+
+fn idx_to_pos(i: usize, dimension: IVec2) -> IVec2 {
+    let cols = dimension.x as usize + 1;
+    IVec2::new((i % cols) as i32, (i / cols) as i32)
 }
 
 fn parse_world(input: &str) -> World {
@@ -51,9 +144,8 @@ fn parse_world(input: &str) -> World {
     let mut dimension = IVec2::ZERO;
     for (y, l) in input.lines().enumerate() {
         for (x, c) in l.chars().enumerate() {
-            if c == '#' {
-                obstacles.push(IVec2::new(x as i32, y as i32));
-            } else if c == '^' {
+            obstacles.push(c == '#');
+            if c == '^' {
                 guard_pos = IVec2::new(x as i32, y as i32);
             }
             dimension.x = dimension.x.max(x as i32);
@@ -72,6 +164,10 @@ fn dir_to_coord(dir: Direction) -> IVec2 {
     }
 }
 
+fn dir_bit(dir: Direction) -> u8 {
+    1 << dir as u8
+}
+
 fn turn(dir: Direction) -> Direction {
     match dir {
         Direction::North => Direction::East,
@@ -81,6 +177,34 @@ fn turn(dir: Direction) -> Direction {
     }
 }
 
+fn create_jumps(obstacles: &[bool], dimension: IVec2) -> Vec<[Jump; 4]> {
+    let mut jumps = vec![[Jump::Exit; 4]; obstacles.len()];
+    for dir in DIRECTIONS {
+        let d = dir_to_coord(dir);
+        /* Visit the tiles so that the neighbour in the walking direction is always done first */
+        let mut xs = (0..=dimension.x).collect::<Vec<_>>();
+        let mut ys = (0..=dimension.y).collect::<Vec<_>>();
+        if d.x > 0 {
+            xs.reverse();
+        }
+        if d.y > 0 {
+            ys.reverse();
+        }
+        for &y in ys.iter() {
+            for &x in xs.iter() {
+                let pos = IVec2::new(x, y);
+                let i = pos_to_idx(pos, dimension).unwrap();
+                jumps[i][dir as usize] = match pos_to_idx(pos + d, dimension) {
+                    None => Jump::Exit,
+                    Some(j) if obstacles[j] => Jump::Stop(pos),
+                    Some(j) => jumps[j][dir as usize],
+                };
+            }
+        }
+    }
+    jumps
+}
+
 #[derive(Debug, PartialEq)]
 enum Action {
     Step,
@@ -90,73 +214,185 @@ enum Action {
 
 fn step(world: &mut World) -> Action {
     let next_step = world.guard_pos + dir_to_coord(world.guard_facing);
-    if world.obstacles.contains(&next_step) {
+    if world.is_obstacle(next_step) {
         world.guard_facing = turn(world.guard_facing);
+        world.visit(world.guard_pos, world.guard_facing);
         return Action::Turn;
     }
-    if next_step.x > world.dimension.x
-        || next_step.y > world.dimension.y
-        || next_step.x < 0
-        || next_step.y < 0
-    {
+    if world.idx(next_step).is_none() {
         return Action::Done;
     }
     world.guard_pos = next_step;
-    world.visited.push(Visited {
-        tile: world.guard_pos,
-        dir: world.guard_facing,
-    });
+    world.visit(world.guard_pos, world.guard_facing);
     Action::Step
 }
 
-fn part1(input: &str) -> (i32, Vec<Visited>) {
+fn part1(input: &str) -> (i32, Vec<IVec2>) {
     let mut world = parse_world(input);
     while step(&mut world) != Action::Done {}
-    let count = world.visited.iter().map(|v| v.tile).unique().count() as i32;
-    (count, world.visited)
+    let visited = world.visited_tiles();
+    (visited.len() as i32, visited)
 }
 
-fn loop_check(mut world: World) -> bool {
+/// Walks the guard from `pos` in `dir`, also stopping in front of the extra `obstacle`
+fn jump(world: &World, pos: IVec2, dir: Direction, obstacle: IVec2) -> Jump {
+    let d = dir_to_coord(dir);
+    let jump = world.jumps[world.idx(pos).unwrap()][dir as usize];
+
+    let dist = (obstacle - pos).dot(d);
+    if dist <= 0 || pos + d * dist != obstacle {
+        // The extra obstacle is not in front of us
+        return jump;
+    }
+    match jump {
+        Jump::Stop(stop) if (stop - pos).dot(d) < dist => jump,
+        _ => Jump::Stop(obstacle - d),
+    }
+}
+
+/// Checks if the guard gets stuck in a loop when `obstacle` is added to the world. Only the
+/// tiles where the guard turns are tracked, which is enough to detect a repeated state.
+fn loop_check(world: &World, obstacle: IVec2) -> bool {
+    let mut turns = vec![0_u8; world.obstacles.len()];
+    let mut pos = world.guard_start;
+    let mut dir = Direction::North;
     loop {
-        match step(&mut world) {
-            Action::Step => {
-                // Check if we have traversed this tile before
-                let len = world.visited.len();
-                if world.visited[0..len - 1].contains(&Visited {
-                    tile: world.guard_pos,
-                    dir: world.guard_facing,
-                }) {
+        match jump(world, pos, dir, obstacle) {
+            Jump::Exit => return false,
+            Jump::Stop(stop) => {
+                let i = world.idx(stop).unwrap();
+                if turns[i] & dir_bit(dir) != 0 {
                     return true;
                 }
+                turns[i] |= dir_bit(dir);
+                pos = stop;
+                dir = turn(dir);
             }
-            Action::Done => {
-                break;
-            }
-            _ => {}
         }
     }
-    false
 }
 
-fn part2(input: &str, visited: &[Visited]) -> i32 {
+/// All positions among `visited` where a new obstruction makes the guard walk in a loop
+fn loop_obstacles(input: &str, visited: &[IVec2]) -> Vec<IVec2> {
     let world = parse_world(input);
 
-    let visited = visited
-        .iter()
-        .map(|v| v.tile)
-        .unique()
-        .collect::<Vec<IVec2>>();
-    let mut acc = 0;
+    visited
+        .par_iter()
+        .filter(|&&tile| {
+            tile != world.guard_start && world.idx(tile).is_some() && !world.is_obstacle(tile)
+        })
+        .filter(|&&tile| loop_check(&world, tile))
+        .copied()
+        .collect()
+}
+
+fn part2(input: &str, visited: &[IVec2]) -> i32 {
+    loop_obstacles(input, visited).len() as i32
+}
+
+/// Draws the map with the guard and the trail so far. Tiles walked vertically are marked with
+/// `|`, horizontally with `-` and both with `+`. Every position in `loops` is marked with `O`.
+fn create_sprite(world: &World, loops: &[IVec2]) -> Frame {
+    let vertical = dir_bit(Direction::North) | dir_bit(Direction::South);
+    let horizontal = dir_bit(Direction::East) | dir_bit(Direction::West);
 
-    for tile in visited[1..].iter() {
-        let mut world = world.clone();
-        world.obstacles.push(*tile);
-        if loop_check(world) {
-            acc += 1;
+    let cols = world.dimension.x as usize + 1;
+    let rows = world.dimension.y as usize + 1;
+    let mut frame = Frame::new(cols, rows, '.');
+    for (i, (&obstacle, &v)) in world.obstacles.iter().zip(world.visited.iter()).enumerate() {
+        let pos = idx_to_pos(i, world.dimension);
+        if obstacle {
+            frame.set(pos, '#', Some(Color::DarkGrey));
+        } else if v & vertical != 0 && v & horizontal != 0 {
+            frame.set(pos, '+', Some(Color::Yellow));
+        } else if v & vertical != 0 {
+            frame.set(pos, '|', Some(Color::Yellow));
+        } else if v & horizontal != 0 {
+            frame.set(pos, '-', Some(Color::Yellow));
         }
     }
+    let guard = match world.guard_facing {
+        Direction::North => '^',
+        Direction::East => '>',
+        Direction::South => 'v',
+        Direction::West => '<',
+    };
+    // The guard stops on its last tile, which may also be one of the loop obstructions
+    frame
+        .layer(&Highlight::new(&[world.guard_pos], guard).color(Color::Red))
+        .layer(&Highlight::new(loops, 'O').color(Color::Green))
+}
 
-    acc
+/// One frame per step or turn of the guard. The last frame also shows where an obstruction
+/// would cause a loop.
+fn patrol_frames(input: &str) -> impl Iterator<Item = Frame> + '_ {
+    let mut world = parse_world(input);
+    let mut done = false;
+    let first = create_sprite(&world, &[]);
+    std::iter::once(first).chain(std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        if step(&mut world) == Action::Done {
+            done = true;
+            let loops = loop_obstacles(input, &world.visited_tiles());
+            return Some(create_sprite(&world, &loops));
+        }
+        Some(create_sprite(&world, &[]))
+    }))
+}
+
+/// Animates the patrol one step at a time. Once the guard has left the map every obstruction
+/// that would cause a loop is shown. Press space to pause and q to quit.
+fn animate(input: &str) -> io::Result<()> {
+    let mut world = parse_world(input);
+    let mut loops = Vec::new();
+    let mut steps = 0;
+    let mut paused = false;
+    let mut done = false;
+
+    let mut terminal = ratatui::init();
+    terminal.clear()?;
+    loop {
+        terminal.draw(|frame| {
+            let status = if done {
+                format!("Steps: {steps} --- Loop obstructions: {}", loops.len())
+            } else {
+                format!("Steps: {steps}")
+            };
+            let [header, area] =
+                Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(frame.area());
+            let text = Paragraph::new(format!("{status} --- Press q to quit"));
+            frame.render_widget(text, header);
+            frame.render_widget(create_sprite(&world, &loops).to_widget(), area);
+        })?;
+
+        if event::poll(Duration::from_millis(30))? {
+            if let event::Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    match key.code {
+                        KeyCode::Char('q') => break,
+                        KeyCode::Char(' ') => paused = !paused,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if paused || done {
+            continue;
+        }
+        match step(&mut world) {
+            Action::Step => steps += 1,
+            Action::Turn => {}
+            Action::Done => {
+                loops = loop_obstacles(input, &world.visited_tiles());
+                done = true;
+            }
+        }
+    }
+    ratatui::restore();
+    Ok(())
 }
 
 #[cfg(test)]
@@ -166,7 +402,7 @@ mod tests {
     #[test]
     fn test_parse_world() {
         let world = parse_world(INPUT);
-        assert_eq!(world.obstacles.len(), 8);
+        assert_eq!(world.obstacles.iter().filter(|&&o| o).count(), 8);
         assert_eq!(world.dimension, IVec2::new(9, 9));
         assert_eq!(world.guard_pos, IVec2::new(4, 6));
     }
@@ -202,6 +438,72 @@ mod tests {
         assert_eq!(world.guard_pos, IVec2::new(8, 2));
     }
 
+    #[test]
+    fn test_jumps() {
+        let world = parse_world(INPUT);
+        let start = world.idx(world.guard_pos).unwrap();
+        assert_eq!(
+            world.jumps[start][Direction::North as usize],
+            Jump::Stop(IVec2::new(4, 1))
+        );
+        assert_eq!(world.jumps[start][Direction::South as usize], Jump::Exit);
+        assert_eq!(
+            world.jumps[start][Direction::West as usize],
+            Jump::Stop(IVec2::new(2, 6))
+        );
+        assert_eq!(
+            jump(&world, world.guard_pos, Direction::North, IVec2::new(4, 3)),
+            Jump::Stop(IVec2::new(4, 4))
+        );
+        assert_eq!(
+            jump(&world, world.guard_pos, Direction::South, IVec2::new(4, 9)),
+            Jump::Stop(IVec2::new(4, 8))
+        );
+    }
+
+    #[test]
+    fn test_loop_check() {
+        let world = parse_world(INPUT);
+        assert!(loop_check(&world, IVec2::new(3, 6)));
+        assert!(loop_check(&world, IVec2::new(7, 9)));
+        assert!(!loop_check(&world, IVec2::new(4, 1)));
+    }
+
+    #[test]
+    fn test_create_sprite() {
+        let mut world = parse_world(INPUT);
+        while step(&mut world) != Action::Done {}
+        let (_, visited) = part1(INPUT);
+        let loops = loop_obstacles(INPUT, &visited);
+        assert_eq!(
+            create_sprite(&world, &loops).to_text(),
+            "....#.....
+....+---+#
+....|...|.
+..#.|...|.
+..+-+-+#|.
+..|.|.|.|.
+.#+O+-+-+.
+.+----OO#.
+#O-O--+|..
+......#O.."
+        );
+    }
+
+    #[test]
+    fn test_patrol_frames() {
+        let frames = patrol_frames(INPUT).collect::<Vec<_>>();
+        let mut world = parse_world(INPUT);
+        assert_eq!(frames[0], create_sprite(&world, &[]));
+        let mut count = 1;
+        while step(&mut world) != Action::Done {
+            count += 1;
+        }
+        assert_eq!(frames.len(), count + 1);
+        let loops = loop_obstacles(INPUT, &world.visited_tiles());
+        assert_eq!(frames.last(), Some(&create_sprite(&world, &loops)));
+    }
+
     #[test]
     fn test_part1() {
         let (count, _) = part1(INPUT);
@@ -1,102 +1,514 @@
-use itertools::Itertools;
+use crate::parse::{self, ParseError};
 
 pub fn main() {
     let input = std::fs::read_to_string("input/day7.txt").expect("No input");
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    let equations = match parse_input(&input) {
+        Ok(equations) => equations,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(2).map(String::as_str) {
+        Some(mode @ ("explain" | "ops")) => {
+            // Operators can be picked with e.g. `cargo run 7 explain "+,*,||"`
+            let operators = match args.get(3).map(|spec| parse_operators(spec)) {
+                Some(Ok(operators)) => operators,
+                Some(Err(e)) => {
+                    println!("{e}");
+                    return;
+                }
+                None if mode == "explain" => part2_operators(),
+                None => {
+                    println!("No operators given");
+                    return;
+                }
+            };
+            if mode == "explain" {
+                for eq in equations.iter() {
+                    if let Some(ops) = solve(eq, &operators) {
+                        println!("{}", format_equation(eq, &ops));
+                    }
+                }
+            } else {
+                println!("Sum: {}", calibration_result(&equations, &operators));
+            }
+        }
+        _ => {
+            println!("Part 1: {}", part1(&equations));
+            println!("Part 2: {}", part2(&equations));
+        }
+    }
 }
 
-fn part1(input: &str) -> i64 {
-    let valid_operators = [Operators::Addition, Operators::Multiplication];
-    input
-        .lines()
-        .fold(0, |acc, l| acc + eval(l, &valid_operators).unwrap_or(0))
+/// A binary operator that the equations are built from. Operators that can be undone are solved
+/// backwards from the test value, which prunes most of the search. If any operator in the set
+/// lacks an inverse every combination is tried from the left instead.
+trait Operator {
+    fn symbol(&self) -> &str;
+    /// Returns `None` if the operation overflows or is not defined for the operands
+    fn apply(&self, lhs: i64, rhs: i64) -> Option<i64>;
+    /// Finds the `lhs` for which `apply(lhs, rhs) == result`. Returns `None` for operators that
+    /// can not be undone, whatever the operands.
+    fn inverse(&self, _result: i64, _rhs: i64) -> Option<Inverse> {
+        None
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Operators {
-    Addition,
-    Multiplication,
-    Concat,
+#[derive(Debug, PartialEq, Eq)]
+enum Inverse {
+    /// No `lhs` gives the result
+    Impossible,
+    Value(i64),
+    /// Every `lhs` gives the result, like multiplying with zero
+    Any,
 }
 
-fn eval(expr: &str, valid_operators: &[Operators]) -> Option<i64> {
-    let (test_val, operands) = expr.split_once(':')?;
-    let test_val = test_val.parse::<i64>().ok()?;
-    let operands = operands
-        .split(' ')
-        .filter_map(|s| s.parse::<i64>().ok())
-        .collect::<Vec<i64>>();
+type Operators = Vec<Box<dyn Operator>>;
 
-    let n_perms = operands.len() - 1;
+struct Addition;
+impl Operator for Addition {
+    fn symbol(&self) -> &str {
+        "+"
+    }
+    fn apply(&self, lhs: i64, rhs: i64) -> Option<i64> {
+        lhs.checked_add(rhs)
+    }
+    fn inverse(&self, result: i64, rhs: i64) -> Option<Inverse> {
+        Some(
+            result
+                .checked_sub(rhs)
+                .map_or(Inverse::Impossible, Inverse::Value),
+        )
+    }
+}
 
-    for operators in itertools::repeat_n(valid_operators, n_perms).multi_cartesian_product() {
-        let mut acc = operands[0];
-        for (operator, operand) in operators.iter().zip(operands[1..].iter()) {
-            match operator {
-                Operators::Addition => acc += operand,
-                Operators::Multiplication => acc *= operand,
-                Operators::Concat => acc = concat(acc, *operand),
-            }
+struct Subtraction;
+impl Operator for Subtraction {
+    fn symbol(&self) -> &str {
+        "-"
+    }
+    fn apply(&self, lhs: i64, rhs: i64) -> Option<i64> {
+        lhs.checked_sub(rhs)
+    }
+    fn inverse(&self, result: i64, rhs: i64) -> Option<Inverse> {
+        Some(
+            result
+                .checked_add(rhs)
+                .map_or(Inverse::Impossible, Inverse::Value),
+        )
+    }
+}
+
+struct Multiplication;
+impl Operator for Multiplication {
+    fn symbol(&self) -> &str {
+        "*"
+    }
+    fn apply(&self, lhs: i64, rhs: i64) -> Option<i64> {
+        lhs.checked_mul(rhs)
+    }
+    fn inverse(&self, result: i64, rhs: i64) -> Option<Inverse> {
+        Some(match (result, rhs) {
+            (0, 0) => Inverse::Any,
+            // `i64::MIN / -1` overflows, so the checked versions are needed
+            _ => match (result.checked_rem(rhs), result.checked_div(rhs)) {
+                (Some(0), Some(lhs)) => Inverse::Value(lhs),
+                _ => Inverse::Impossible,
+            },
+        })
+    }
+}
+
+/// Integer division rounding towards zero. Many `lhs` give the same result, so it has no inverse.
+struct Division;
+impl Operator for Division {
+    fn symbol(&self) -> &str {
+        "/"
+    }
+    fn apply(&self, lhs: i64, rhs: i64) -> Option<i64> {
+        lhs.checked_div(rhs)
+    }
+}
+
+struct Xor;
+impl Operator for Xor {
+    fn symbol(&self) -> &str {
+        "^"
+    }
+    fn apply(&self, lhs: i64, rhs: i64) -> Option<i64> {
+        Some(lhs ^ rhs)
+    }
+    fn inverse(&self, result: i64, rhs: i64) -> Option<Inverse> {
+        Some(Inverse::Value(result ^ rhs))
+    }
+}
+
+/// Appends the digits of `rhs` to `lhs`, written in the given base. Only defined for
+/// non-negative operands.
+struct Concat {
+    base: i64,
+    symbol: String,
+}
+impl Concat {
+    fn new(base: i64) -> Self {
+        assert!(base >= 2, "Invalid base {base}");
+        let symbol = match base {
+            10 => "||".to_string(),
+            base => format!("||{base}"),
+        };
+        Self { base, symbol }
+    }
+    fn shift(&self, rhs: i64) -> Option<i64> {
+        let digits = rhs.checked_ilog(self.base).unwrap_or(0) + 1;
+        self.base.checked_pow(digits)
+    }
+}
+impl Operator for Concat {
+    fn symbol(&self) -> &str {
+        &self.symbol
+    }
+    fn apply(&self, lhs: i64, rhs: i64) -> Option<i64> {
+        if lhs < 0 || rhs < 0 {
+            return None;
         }
-        if acc == test_val {
-            return Some(test_val);
+        lhs.checked_mul(self.shift(rhs)?)?.checked_add(rhs)
+    }
+    fn inverse(&self, result: i64, rhs: i64) -> Option<Inverse> {
+        if result < 0 || rhs < 0 {
+            return Some(Inverse::Impossible);
         }
+        Some(match self.shift(rhs) {
+            Some(shift) if result % shift == rhs => Inverse::Value(result / shift),
+            // If the shift overflows the result can only be `rhs` itself, with `lhs` being zero
+            None if result == rhs => Inverse::Value(0),
+            _ => Inverse::Impossible,
+        })
     }
+}
+
+fn part1_operators() -> Operators {
+    vec![Box::new(Addition), Box::new(Multiplication)]
+}
+
+fn part2_operators() -> Operators {
+    vec![
+        Box::new(Addition),
+        Box::new(Multiplication),
+        Box::new(Concat::new(10)),
+    ]
+}
+
+/// Parses a comma separated list of operator symbols, such as `+,*,||,||2,-,^,/`
+fn parse_operators(spec: &str) -> Result<Operators, String> {
+    spec.split(',')
+        .map(|s| -> Result<Box<dyn Operator>, String> {
+            match s.trim() {
+                "+" => Ok(Box::new(Addition)),
+                "-" => Ok(Box::new(Subtraction)),
+                "*" => Ok(Box::new(Multiplication)),
+                "/" => Ok(Box::new(Division)),
+                "^" => Ok(Box::new(Xor)),
+                "||" => Ok(Box::new(Concat::new(10))),
+                s => match s.strip_prefix("||").map(str::parse::<i64>) {
+                    Some(Ok(base)) if base >= 2 => Ok(Box::new(Concat::new(base))),
+                    _ => Err(format!("Unknown operator '{s}'")),
+                },
+            }
+        })
+        .collect()
+}
+
+fn calibration_result(equations: &[Equation], valid_operators: &[Box<dyn Operator>]) -> i64 {
+    equations
+        .iter()
+        .fold(0, |acc, eq| acc + eval(eq, valid_operators).unwrap_or(0))
+}
 
-    Some(0)
+fn part1(equations: &[Equation]) -> i64 {
+    calibration_result(equations, &part1_operators())
 }
 
-fn concat(lhs: i64, rhs: i64) -> i64 {
-    let shift = rhs.ilog10() + 1;
-    rhs + lhs * 10_i64.pow(shift)
+#[derive(Debug, PartialEq, Eq)]
+struct Equation {
+    test_val: i64,
+    operands: Vec<i64>,
 }
 
-fn part2(input: &str) -> i64 {
-    let valid_operators = [
-        Operators::Addition,
-        Operators::Multiplication,
-        Operators::Concat,
-    ];
+/// Parses `190: 10 19`
+fn parse_equation(expr: &str, line: usize) -> Result<Equation, ParseError> {
+    let Some((test_val, operands)) = expr.split_once(':') else {
+        return Err(ParseError::new(line, format!("expected ':' in '{expr}'")));
+    };
+    let test_val = test_val
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| ParseError::new(line, format!("invalid test value '{test_val}'")))?;
+    let operands = parse::fields::<i64>(operands, line)?;
+    if operands.is_empty() {
+        return Err(ParseError::new(line, "no operands"));
+    }
+    Ok(Equation { test_val, operands })
+}
+
+/// One equation per line, skipping blank lines
+fn parse_input(input: &str) -> Result<Vec<Equation>, ParseError> {
     input
         .lines()
-        .fold(0, |acc, l| acc + eval(l, &valid_operators).unwrap_or(0))
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| parse_equation(l, i + 1))
+        .collect()
+}
+
+/// Returns the test value if the equation can be made true with the given operators
+fn eval(eq: &Equation, valid_operators: &[Box<dyn Operator>]) -> Option<i64> {
+    solve(eq, valid_operators).map(|_| eq.test_val)
+}
+
+/// Finds a sequence of operators that makes the equation true
+fn solve<'a>(
+    eq: &Equation,
+    valid_operators: &'a [Box<dyn Operator>],
+) -> Option<Vec<&'a dyn Operator>> {
+    let valid_operators = valid_operators
+        .iter()
+        .map(|o| o.as_ref())
+        .collect::<Vec<_>>();
+    let mut ops = Vec::with_capacity(eq.operands.len() - 1);
+    let last = *eq.operands.last().unwrap();
+    if valid_operators
+        .iter()
+        .all(|o| o.inverse(eq.test_val, last).is_some())
+    {
+        if solve_backwards(eq.test_val, &eq.operands, &valid_operators, &mut ops) {
+            ops.reverse();
+            return Some(ops);
+        }
+    } else if solve_forwards(
+        eq.operands[0],
+        &eq.operands[1..],
+        Some(eq.test_val),
+        &valid_operators,
+        &mut ops,
+    ) {
+        return Some(ops);
+    }
+    None
+}
+
+/// Operators are evaluated left to right, so we work backwards from the test value and undo the
+/// last operation at each step. Branches where the last operation can not be undone are pruned
+/// right away. Pushes the operators to `ops` in reverse order.
+fn solve_backwards<'a>(
+    target: i64,
+    operands: &[i64],
+    valid_operators: &[&'a dyn Operator],
+    ops: &mut Vec<&'a dyn Operator>,
+) -> bool {
+    let (&last, rest) = operands.split_last().unwrap();
+    if rest.is_empty() {
+        return target == last;
+    }
+
+    for &op in valid_operators {
+        match op.inverse(target, last) {
+            None | Some(Inverse::Impossible) => {}
+            Some(Inverse::Value(prev)) => {
+                ops.push(op);
+                if solve_backwards(prev, rest, valid_operators, ops) {
+                    return true;
+                }
+                ops.pop();
+            }
+            Some(Inverse::Any) => {
+                // Any value works for the rest, as long as it can be evaluated
+                let mut prefix = Vec::with_capacity(rest.len() - 1);
+                if solve_forwards(rest[0], &rest[1..], None, valid_operators, &mut prefix) {
+                    ops.push(op);
+                    ops.extend(prefix.into_iter().rev());
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Tries every combination from the left, skipping branches that overflow. Without a target any
+/// combination that can be evaluated is accepted.
+fn solve_forwards<'a>(
+    acc: i64,
+    operands: &[i64],
+    target: Option<i64>,
+    valid_operators: &[&'a dyn Operator],
+    ops: &mut Vec<&'a dyn Operator>,
+) -> bool {
+    let Some((&next, rest)) = operands.split_first() else {
+        return target.is_none_or(|t| t == acc);
+    };
+
+    for &op in valid_operators {
+        if let Some(acc) = op.apply(acc, next) {
+            ops.push(op);
+            if solve_forwards(acc, rest, target, valid_operators, ops) {
+                return true;
+            }
+            ops.pop();
+        }
+    }
+    false
+}
+
+fn format_equation(eq: &Equation, ops: &[&dyn Operator]) -> String {
+    let mut out = format!("{} = {}", eq.test_val, eq.operands[0]);
+    for (op, operand) in ops.iter().zip(eq.operands[1..].iter()) {
+        out += &format!(" {} {operand}", op.symbol());
+    }
+    out
+}
+
+fn part2(equations: &[Equation]) -> i64 {
+    calibration_result(equations, &part2_operators())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Evaluates the operands left to right, returning `None` on overflow
+    fn evaluate(operands: &[i64], ops: &[&dyn Operator]) -> Option<i64> {
+        let mut acc = operands[0];
+        for (op, &operand) in ops.iter().zip(operands[1..].iter()) {
+            acc = op.apply(acc, operand)?;
+        }
+        Some(acc)
+    }
+
+    fn equation(expr: &str) -> Equation {
+        parse_equation(expr, 1).unwrap()
+    }
+
+    fn explain(expr: &str, valid_operators: &[Box<dyn Operator>]) -> Option<String> {
+        let eq = equation(expr);
+        let ops = solve(&eq, valid_operators)?;
+        assert_eq!(evaluate(&eq.operands, &ops), Some(eq.test_val));
+        Some(format_equation(&eq, &ops))
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(INPUT), 3749);
+        assert_eq!(part1(&parse_input(INPUT).unwrap()), 3749);
     }
 
     #[test]
     fn test_eval() {
-        let part1_operators = [Operators::Addition, Operators::Multiplication];
-        assert_eq!(eval("190: 10 19", &part1_operators), Some(190));
-        assert_eq!(eval("156: 15, 6", &part1_operators), Some(0));
-        assert_eq!(eval("156 15 6", &part1_operators), None); // Faulty format
-        let part2_operators = [
-            Operators::Addition,
-            Operators::Multiplication,
-            Operators::Concat,
-        ];
-        assert_eq!(eval("156: 15 6", &part2_operators), Some(156));
-        assert_eq!(eval("7290: 6 8 6 15", &part2_operators), Some(7290));
+        let part1_operators = part1_operators();
+        assert_eq!(eval(&equation("190: 10 19"), &part1_operators), Some(190));
+        assert_eq!(eval(&equation("156: 15 6"), &part1_operators), None);
+        let part2_operators = part2_operators();
+        assert_eq!(eval(&equation("156: 15 6"), &part2_operators), Some(156));
+        assert_eq!(
+            eval(&equation("7290: 6 8 6 15"), &part2_operators),
+            Some(7290)
+        );
+    }
+
+    #[test]
+    fn test_parse_input() {
+        let equations = parse_input("190: 10 19\n\n83: 17 5\n").unwrap();
+        assert_eq!(
+            equations[1],
+            Equation {
+                test_val: 83,
+                operands: vec![17, 5]
+            }
+        );
+        let error = |input| parse_input(input).unwrap_err().to_string();
+        assert_eq!(
+            error("190: 10 19\n156: 15, 6"),
+            "line 2: invalid value '15,'"
+        );
+        assert_eq!(error("156 15 6"), "line 1: expected ':' in '156 15 6'");
+        assert_eq!(
+            error("190: 10 19\n\nx: 1"),
+            "line 3: invalid test value 'x'"
+        );
+        assert_eq!(error("156:"), "line 1: no operands");
+    }
+
+    #[test]
+    fn test_solve() {
+        let ops = part2_operators();
+        assert_eq!(
+            explain("292: 11 6 16 20", &ops).as_deref(),
+            Some("292 = 11 + 6 * 16 + 20")
+        );
+        assert_eq!(
+            explain("7290: 6 8 6 15", &ops).as_deref(),
+            Some("7290 = 6 * 8 || 6 * 15")
+        );
+        assert_eq!(explain("0: 5 3 0", &ops).as_deref(), Some("0 = 5 + 3 * 0"));
+        assert_eq!(explain("161011: 16 10 13", &ops), None);
+    }
+
+    #[test]
+    fn test_custom_operators() {
+        let ops = parse_operators("-,^").unwrap();
+        assert_eq!(explain("2: 5 3 4", &ops).as_deref(), Some("2 = 5 ^ 3 - 4"));
+        let ops = parse_operators("+,/").unwrap();
+        assert_eq!(explain("3: 7 2 0", &ops).as_deref(), Some("3 = 7 / 2 + 0"));
+        assert_eq!(explain("7: 7 0 1", &ops).as_deref(), Some("7 = 7 + 0 / 1"));
+        assert_eq!(explain("1: 7 0 1", &ops), None); // Division by zero is skipped
+        let ops = parse_operators("||2").unwrap();
+        assert_eq!(
+            explain("13: 1 2 1", &ops).as_deref(),
+            Some("13 = 1 ||2 2 ||2 1")
+        );
+        assert_eq!(
+            parse_operators("+,%").err().as_deref(),
+            Some("Unknown operator '%'")
+        );
+        assert!(parse_operators("||1").is_err());
+        assert!(parse_operators("||x").is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let ops = part2_operators();
+        assert_eq!(explain("0: 9223372036854775807 2 0", &ops).as_deref(), None);
+        let ops = parse_operators("*,/").unwrap();
+        assert_eq!(
+            explain("4611686018427387903: 9223372036854775807 2 1", &ops).as_deref(),
+            Some("4611686018427387903 = 9223372036854775807 / 2 * 1")
+        );
+        let ops = parse_operators("-,*").unwrap();
+        assert_eq!(eval(&equation("-9223372036854775808: 5 -1"), &ops), None);
+        assert_eq!(
+            Multiplication.inverse(i64::MIN, -1),
+            Some(Inverse::Impossible)
+        );
+        assert_eq!(Division.inverse(6, 2), None);
     }
 
     #[test]
     fn test_concat() {
-        assert_eq!(concat(1, 2), 12);
-        assert_eq!(concat(2, 45), 245);
-        assert_eq!(concat(15, 6), 156);
+        let concat = Concat::new(10);
+        assert_eq!(concat.apply(1, 2), Some(12));
+        assert_eq!(concat.apply(2, 45), Some(245));
+        assert_eq!(concat.apply(15, 6), Some(156));
+        assert_eq!(concat.apply(15, 0), Some(150));
+        assert_eq!(concat.apply(i64::MAX, 1), None);
+        assert_eq!(concat.inverse(245, 45), Some(Inverse::Value(2)));
+        assert_eq!(concat.inverse(245, 5), Some(Inverse::Value(24)));
+        assert_eq!(concat.inverse(245, 4), Some(Inverse::Impossible));
+        assert_eq!(Concat::new(2).apply(1, 2), Some(6));
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(INPUT), 11387);
+        assert_eq!(part2(&parse_input(INPUT).unwrap()), 11387);
     }
 
     const INPUT: &str = "190: 10 19
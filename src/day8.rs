@@ -1,8 +1,43 @@
+use std::collections::BTreeMap;
+
+use crossterm::style::{Color, Stylize};
 use glam::IVec2;
 use itertools::Itertools;
 
 pub fn main() {
     let input = std::fs::read_to_string("input/day8.txt").expect("No input");
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("map") {
+        // E.g. `cargo run 8 map 3 color collinear` for up to the third harmonic in colour. The
+        // arguments can come in any order and every harmonic is shown by default.
+        let mut harmonics = Harmonics::Unbounded;
+        let mut color = false;
+        let mut collinear = false;
+        for arg in args.iter().skip(3) {
+            match arg.as_str() {
+                "all" => harmonics = Harmonics::Unbounded,
+                "first" => harmonics = Harmonics::First,
+                "color" => color = true,
+                "collinear" => collinear = true,
+                n if n.starts_with(|c: char| c.is_ascii_digit()) => match n.parse() {
+                    Ok(n) => harmonics = Harmonics::UpTo(n),
+                    Err(_) => {
+                        println!("Invalid harmonic '{n}'");
+                        return;
+                    }
+                },
+                other => {
+                    println!("Unknown argument '{other}'");
+                    return;
+                }
+            }
+        }
+        let map = parse_map(&input);
+        let antinodes = find_antinodes(&map, harmonics, collinear);
+        println!("{}", render(&map, &antinodes, color));
+        println!("Antinodes: {}", antinodes.len());
+        return;
+    }
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
 }
@@ -11,73 +46,159 @@ fn pos_to_idx(pos: IVec2, rows: usize, cols: usize) -> Option<usize> {
     if pos.x >= cols as i32 || pos.y >= rows as i32 || pos.x < 0 || pos.y < 0 {
         return None;
     }
-    Some((pos.y * (rows as i32) + pos.x) as usize)
+    Some((pos.y * (cols as i32) + pos.x) as usize)
 }
 
 fn idx_to_pos(i: usize, rows: usize, cols: usize) -> Option<IVec2> {
     if i >= rows * cols {
         return None;
     }
-    let x = i % rows;
-    let y = i / rows;
+    let x = i % cols;
+    let y = i / cols;
     Some(IVec2 {
         x: x as i32,
         y: y as i32,
     })
 }
 
-fn part1(input: &str) -> i32 {
+/// Which antinodes an antenna pair creates. Harmonic `k` lies `k` times the distance between the
+/// antennas away from one of them, so harmonic 0 is on the antenna itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Harmonics {
+    /// Only harmonic 1, as in part 1
+    First,
+    /// Harmonics 0 to N
+    UpTo(usize),
+    /// Every harmonic within the map, as in part 2
+    Unbounded,
+}
+
+struct Map {
+    rows: usize,
+    cols: usize,
+    tiles: Vec<char>,
+    /// Antenna positions grouped by frequency
+    antennas: BTreeMap<char, Vec<IVec2>>,
+}
+
+fn parse_map(input: &str) -> Map {
     let cols = input.lines().next().expect("Empty input").len();
     let rows = input.lines().count();
-
     // Remove new lines
-    let input = input.lines().collect::<String>();
-
-    let mut nodes = Vec::new();
-    for (i, first) in input.chars().enumerate() {
-        if first.is_alphanumeric() {
-            let first_pos = idx_to_pos(i, rows, cols).expect("Iterated to out of bounds");
-            for (j, second) in input.chars().enumerate() {
-                if second == first && i != j {
-                    let second_pos = idx_to_pos(j, rows, cols).expect("Iterated to out of bounds");
-                    let node_pos = 2 * first_pos - second_pos;
-                    if pos_to_idx(node_pos, rows, cols).is_some() {
-                        nodes.push(node_pos);
-                    }
-                }
-            }
+    let tiles = input.lines().flat_map(|l| l.chars()).collect::<Vec<_>>();
+
+    let mut antennas: BTreeMap<char, Vec<IVec2>> = BTreeMap::new();
+    for (i, &c) in tiles.iter().enumerate() {
+        if c.is_alphanumeric() {
+            let pos = idx_to_pos(i, rows, cols).expect("Iterated to out of bounds");
+            antennas.entry(c).or_default().push(pos);
         }
     }
+    Map {
+        rows,
+        cols,
+        tiles,
+        antennas,
+    }
+}
 
-    nodes.iter().unique().count() as i32
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
 }
 
-fn part2(input: &str) -> i32 {
-    let cols = input.lines().next().expect("Empty input").len();
-    let rows = input.lines().count();
+/// Maps every antinode to the frequency of the first antenna pair that created it. With
+/// `collinear` set the line through two antennas is walked in the smallest grid step, which also
+/// includes the grid points between the antennas and between consecutive harmonics.
+fn find_antinodes(map: &Map, harmonics: Harmonics, collinear: bool) -> BTreeMap<usize, char> {
+    let (rows, cols) = (map.rows, map.cols);
+    let (first_harmonic, last_harmonic) = match harmonics {
+        Harmonics::First => (1, 1),
+        Harmonics::UpTo(n) => (0, n),
+        Harmonics::Unbounded => (0, usize::MAX),
+    };
 
-    // Remove new lines
-    let input = input.lines().collect::<String>();
-
-    let mut nodes = Vec::new();
-    for (i, first) in input.chars().enumerate() {
-        if first.is_alphanumeric() {
-            let first_pos = idx_to_pos(i, rows, cols).expect("Iterated to out of bounds");
-            for (j, second) in input.chars().enumerate() {
-                if second == first && i != j {
-                    let second_pos = idx_to_pos(j, rows, cols).expect("Iterated to out of bounds");
-                    let offset = first_pos - second_pos;
-                    let mut node_pos = first_pos;
-                    while pos_to_idx(node_pos, rows, cols).is_some() {
-                        nodes.push(node_pos);
-                        node_pos += offset;
-                    }
+    let mut nodes = BTreeMap::new();
+    for (&frequency, positions) in map.antennas.iter() {
+        for (a, b) in positions.iter().tuple_combinations() {
+            let offset = a - b;
+            let steps_per_harmonic = if collinear {
+                gcd(offset.x, offset.y) as usize
+            } else {
+                1
+            };
+            let step = offset / steps_per_harmonic as i32;
+
+            // Walk away from the other antenna on both ends
+            for (start, dir) in [(*a, step), (*b, -step)] {
+                for k in first_harmonic * steps_per_harmonic
+                    ..=last_harmonic.saturating_mul(steps_per_harmonic)
+                {
+                    let Some(idx) = pos_to_idx(start + dir * k as i32, rows, cols) else {
+                        break;
+                    };
+                    nodes.entry(idx).or_insert(frequency);
+                }
+            }
+            if collinear {
+                for k in 1..steps_per_harmonic {
+                    let idx = pos_to_idx(b + step * k as i32, rows, cols).unwrap();
+                    nodes.entry(idx).or_insert(frequency);
                 }
             }
         }
     }
+    nodes
+}
+
+fn frequency_color(frequency: char) -> Color {
+    const PALETTE: [Color; 6] = [
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+    ];
+    PALETTE[frequency as usize % PALETTE.len()]
+}
+
+/// Draws the antenna map with `#` on every antinode that is not covered by an antenna. With
+/// `color` set both antennas and antinodes are coloured by frequency.
+fn render(map: &Map, antinodes: &BTreeMap<usize, char>, color: bool) -> String {
+    map.tiles
+        .chunks(map.cols)
+        .enumerate()
+        .map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(x, &c)| {
+                    let idx = y * map.cols + x;
+                    let (c, frequency) = match antinodes.get(&idx) {
+                        Some(&f) if c == '.' => ('#', f),
+                        _ => (c, c),
+                    };
+                    if color && c != '.' {
+                        c.with(frequency_color(frequency)).to_string()
+                    } else {
+                        c.to_string()
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn part1(input: &str) -> i32 {
+    find_antinodes(&parse_map(input), Harmonics::First, false).len() as i32
+}
 
-    nodes.iter().unique().count() as i32
+fn part2(input: &str) -> i32 {
+    find_antinodes(&parse_map(input), Harmonics::Unbounded, false).len() as i32
 }
 
 #[cfg(test)]
@@ -94,6 +215,55 @@ mod tests {
         assert_eq!(part2(INPUT), 34);
     }
 
+    #[test]
+    fn test_harmonics() {
+        let map = parse_map(INPUT);
+        assert_eq!(find_antinodes(&map, Harmonics::UpTo(0), false).len(), 7);
+        assert_eq!(find_antinodes(&map, Harmonics::UpTo(1), false).len(), 20);
+        assert_eq!(find_antinodes(&map, Harmonics::UpTo(100), false).len(), 34);
+    }
+
+    #[test]
+    fn test_collinear() {
+        let map = parse_map("a.....\n......\n..a...\n......\n......\n......");
+        let antinodes = find_antinodes(&map, Harmonics::Unbounded, false);
+        assert_eq!(
+            render(&map, &antinodes, false),
+            "a.....\n......\n..a...\n......\n....#.\n......"
+        );
+        let antinodes = find_antinodes(&map, Harmonics::Unbounded, true);
+        assert_eq!(
+            render(&map, &antinodes, false),
+            "a.....\n.#....\n..a...\n...#..\n....#.\n.....#"
+        );
+        let antinodes = find_antinodes(&map, Harmonics::First, true);
+        assert_eq!(
+            render(&map, &antinodes, false),
+            "a.....\n.#....\n..a...\n......\n....#.\n......"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let map = parse_map(INPUT);
+        let antinodes = find_antinodes(&map, Harmonics::First, false);
+        assert_eq!(
+            render(&map, &antinodes, false),
+            "......#....#
+...#....0...
+....#0....#.
+..#....0....
+....0....#..
+.#....A.....
+...#........
+#......#....
+........A...
+.........A..
+..........#.
+..........#."
+        );
+    }
+
     #[test]
     fn test_part2_single_channel() {
         let input = "T....#....
@@ -1,115 +1,298 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crossterm::style::Color;
+use glam::IVec2;
+
+use crate::render::{Frame, Render};
+
 pub fn main() {
     let input = std::fs::read_to_string("input/day9.txt").expect("No input");
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(2).map(String::as_str) == Some("trace") {
+        // `cargo run 9 trace files` traces the part 2 strategy, part 1 otherwise. Add `color` to
+        // colour the files.
+        let disk = read_input(&input);
+        let compaction = match args.get(3).map(String::as_str) {
+            Some("files") => compact_files(&disk),
+            _ => compact_blocks(&disk),
+        };
+        let color = args[3..].iter().any(|a| a == "color");
+        println!("{}", trace(&disk, &compaction, color));
+        return;
+    }
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
 }
 
+/// A file, or a part of one, occupying `len` blocks from `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Extent {
+    pub id: usize,
+    pub start: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    pub start: usize,
+    pub len: usize,
+}
+
+/// `len` blocks of file `id` moved from `from` to `to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Move {
+    pub id: usize,
+    pub from: usize,
+    pub to: usize,
+    pub len: usize,
+}
+
 #[derive(Debug)]
-struct Block {
-    pub data: Vec<usize>,
+struct Compaction {
+    pub disk: Disk,
+    pub moves: Vec<Move>,
+    /// Files that stayed in place even though there was free space to their left
+    pub stuck: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Disk {
+    /// Sorted by start
+    pub files: Vec<Extent>,
+    /// The gaps between the files, sorted by start
+    pub free: Vec<Span>,
     pub size: usize,
 }
-impl Block {
-    pub fn free(&self) -> usize {
-        self.size - self.data.len()
+impl Disk {
+    pub fn from_files(mut files: Vec<Extent>, size: usize) -> Self {
+        files.retain(|f| f.len > 0);
+        files.sort_by_key(|f| f.start);
+        let mut free = Vec::new();
+        let mut pos = 0;
+        for f in files.iter() {
+            if f.start > pos {
+                free.push(Span {
+                    start: pos,
+                    len: f.start - pos,
+                });
+            }
+            pos = f.start + f.len;
+        }
+        if size > pos {
+            free.push(Span {
+                start: pos,
+                len: size - pos,
+            });
+        }
+        Self { files, free, size }
     }
-}
 
-#[allow(unused)]
-fn memory_to_string(memory: &[Block]) -> String {
-    let mut output = String::new();
-    for m in memory {
-        for d in &m.data {
-            output += &d.to_string();
-        }
-        output += &".".repeat(m.free());
+    /// Free spans before the last file. The free space at the end of the disk is not counted.
+    pub fn gaps(&self) -> &[Span] {
+        let end = self.files.last().map_or(0, |f| f.start + f.len);
+        let n = self.free.partition_point(|s| s.start < end);
+        &self.free[..n]
+    }
+
+    pub fn checksum(&self) -> i64 {
+        self.files.iter().fold(0, |acc, f| {
+            // Sum of all positions from start to start + len - 1
+            let positions = f.start * f.len + f.len * (f.len - 1) / 2;
+            acc + (f.id * positions) as i64
+        })
     }
-    output
 }
 
-fn part1(input: &str) -> i64 {
-    let mut memory = read_input(input);
-    let mut forward = 0;
-    let mut backward = memory.len() - 1;
-    while forward < backward {
-        while memory[forward].free() == 0 {
-            forward += 1;
-        }
-        while memory[backward].data.is_empty() {
-            backward -= 1;
-        }
+const FILE_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
 
-        while memory[forward].free() > 0 && !memory[backward].data.is_empty() {
-            let to_move = memory[backward].data.pop().unwrap();
-            memory[forward].data.push(to_move);
-        }
-        if memory[forward].free() == 0 {
-            forward += 1;
-        }
-        if memory[backward].data.is_empty() {
-            backward -= 1;
+/// One row with a cell per block. Ids above 9 are shown by their last digit to keep one
+/// character per block, and the colour tells neighbouring files with the same digit apart.
+impl Render for Disk {
+    fn render(&self, frame: &mut Frame) {
+        for f in self.files.iter() {
+            let id = char::from_digit((f.id % 10) as u32, 10).unwrap();
+            let color = FILE_COLORS[f.id % FILE_COLORS.len()];
+            for block in f.start..f.start + f.len {
+                frame.set(IVec2::new(block as i32, 0), id, Some(color));
+            }
         }
     }
+}
 
-    memory
-        .iter()
-        .flat_map(|m| &m.data)
-        .enumerate()
-        .fold(0, |acc, (i, d)| acc + i * d) as i64
+fn memory_frame(disk: &Disk) -> Frame {
+    Frame::new(disk.size, 1, '.').layer(disk)
 }
 
-fn read_input(input: &str) -> Vec<Block> {
-    let mut memory = Vec::new();
+fn memory_to_string(disk: &Disk) -> String {
+    memory_frame(disk).to_text()
+}
+
+fn read_input(input: &str) -> Disk {
+    let mut files = Vec::new();
 
     let mut occupied = true;
     let mut id = 0;
+    let mut pos = 0;
     for c in input.chars() {
-        if let Some(size) = c.to_digit(10) {
-            let size = size as usize;
+        if let Some(len) = c.to_digit(10) {
+            let len = len as usize;
             if occupied {
-                memory.push(Block {
-                    data: vec![id; size],
-                    size,
+                files.push(Extent {
+                    id,
+                    start: pos,
+                    len,
                 });
                 id += 1;
-            } else {
-                memory.push(Block { data: vec![], size });
             }
+            pos += len;
             occupied = !occupied;
         }
     }
-    memory
+    Disk::from_files(files, pos)
 }
 
-fn part2(input: &str) -> i64 {
-    let mut memory = read_input(input);
+/// Moves single blocks from the end of the disk into the leftmost free space, splitting files
+fn compact_blocks(disk: &Disk) -> Compaction {
+    let mut files = disk.files.clone();
+    let mut moved = Vec::new();
+    let mut moves = Vec::new();
+    let mut back = files.len();
 
-    let mut backward = memory.len() - 1;
-    while backward > 0 {
-        while memory[backward].data.is_empty() {
-            backward -= 1;
+    'outer: for span in disk.free.iter() {
+        let mut pos = span.start;
+        let mut room = span.len;
+        while room > 0 && back > 0 {
+            let f = &mut files[back - 1];
+            if f.start < pos {
+                // Everything left of this span is already packed
+                break 'outer;
+            }
+            let len = room.min(f.len);
+            moved.push(Extent {
+                id: f.id,
+                start: pos,
+                len,
+            });
+            f.len -= len;
+            moves.push(Move {
+                id: f.id,
+                from: f.start + f.len,
+                to: pos,
+                len,
+            });
+            pos += len;
+            room -= len;
+            if f.len == 0 {
+                back -= 1;
+            }
         }
+    }
 
-        for i in 0..backward {
-            if memory[i].free() > 0 && memory[i].free() >= memory[backward].data.len() {
-                while let Some(v) = memory[backward].data.pop() {
-                    memory[i].data.push(v);
-                }
-                break;
+    files.append(&mut moved);
+    Compaction {
+        disk: Disk::from_files(files, disk.size),
+        moves,
+        stuck: vec![],
+    }
+}
+
+/// Moves each file, starting with the highest id, to the leftmost free span it fits in. Free
+/// spans are kept in one min-heap of start positions per size, so finding the best span only has
+/// to look at the smallest start for every size that is large enough.
+fn compact_files(disk: &Disk) -> Compaction {
+    // Free spans around an empty file are merged, so they can be longer than a single digit
+    let max_len = disk.free.iter().map(|span| span.len).max().unwrap_or(0);
+    let mut free_by_size = vec![BinaryHeap::new(); max_len + 1];
+    for span in disk.free.iter() {
+        free_by_size[span.len].push(Reverse(span.start));
+    }
+
+    let mut files = disk.files.clone();
+    let mut moves = Vec::new();
+    let mut stuck = Vec::new();
+    for f in files.iter_mut().rev() {
+        let best = (f.len..free_by_size.len())
+            .filter_map(|size| free_by_size[size].peek().map(|s| (s.0, size)))
+            .min()
+            .filter(|&(start, _)| start < f.start);
+        let Some((start, size)) = best else {
+            let first_free = free_by_size.iter().filter_map(|h| h.peek()).min();
+            if first_free.is_some_and(|s| s.0 < f.start) {
+                stuck.push(f.id);
             }
+            continue;
+        };
+        free_by_size[size].pop();
+        if size > f.len {
+            free_by_size[size - f.len].push(Reverse(start + f.len));
         }
-        backward -= 1;
+        // The space left behind is to the right of all remaining files, so it is never reused
+        moves.push(Move {
+            id: f.id,
+            from: f.start,
+            to: start,
+            len: f.len,
+        });
+        f.start = start;
     }
 
-    memory
-        .iter_mut()
-        .flat_map(|m| {
-            // Zero-padding because we are not perfectly packed like in part 1
-            m.data.append(&mut vec![0; m.free()]);
-            &m.data
-        })
-        .enumerate()
-        .fold(0, |acc, (i, d)| acc + i * d) as i64
+    Compaction {
+        disk: Disk::from_files(files, disk.size),
+        moves,
+        stuck,
+    }
+}
+
+/// Lists every move with the disk layout before and after, followed by fragmentation metrics
+fn trace(before: &Disk, compaction: &Compaction, color: bool) -> String {
+    let after = &compaction.disk;
+    let memory = |disk| match color {
+        true => memory_frame(disk).to_ansi(),
+        false => memory_to_string(disk),
+    };
+    let mut out = format!("Before: {}\n", memory(before));
+    for m in compaction.moves.iter() {
+        out += &format!(
+            "Move file {}: {} blocks from {} to {}\n",
+            m.id, m.len, m.from, m.to
+        );
+    }
+    out += &format!("After:  {}\n", memory(after));
+    out += &format!(
+        "Free spans: {} -> {}\n",
+        before.gaps().len(),
+        after.gaps().len()
+    );
+    out += &format!(
+        "Largest free span: {} -> {}\n",
+        before.gaps().iter().map(|s| s.len).max().unwrap_or(0),
+        after.gaps().iter().map(|s| s.len).max().unwrap_or(0)
+    );
+    out += &format!(
+        "Files that could not move: {}",
+        compaction
+            .stuck
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    out
+}
+
+fn part1(input: &str) -> i64 {
+    compact_blocks(&read_input(input)).disk.checksum()
+}
+
+fn part2(input: &str) -> i64 {
+    compact_files(&read_input(input)).disk.checksum()
 }
 
 #[cfg(test)]
@@ -118,6 +301,71 @@ mod tests {
 
     const INPUT: &str = "2333133121414131402";
 
+    #[test]
+    fn test_read_input() {
+        let disk = read_input(INPUT);
+        assert_eq!(
+            memory_to_string(&disk),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+        assert_eq!(disk.free.len(), 8);
+        assert_eq!(disk.free[0], Span { start: 2, len: 3 });
+        assert_eq!(memory_to_string(&read_input("12345")), "0..111....22222");
+    }
+
+    #[test]
+    fn test_compact() {
+        let disk = read_input(INPUT);
+        assert_eq!(
+            memory_to_string(&compact_blocks(&disk).disk),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(
+            memory_to_string(&compact_files(&disk).disk),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(
+            memory_to_string(&compact_blocks(&read_input("12345")).disk),
+            "022111222......"
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        let disk = read_input("12345");
+        assert_eq!(
+            trace(&disk, &compact_blocks(&disk), false),
+            "Before: 0..111....22222
+Move file 2: 2 blocks from 13 to 1
+Move file 2: 3 blocks from 10 to 6
+After:  022111222......
+Free spans: 2 -> 0
+Largest free span: 4 -> 0
+Files that could not move: "
+        );
+        let disk = read_input(INPUT);
+        let compaction = compact_files(&disk);
+        assert_eq!(
+            compaction.moves[..2],
+            [
+                Move {
+                    id: 9,
+                    from: 40,
+                    to: 2,
+                    len: 2
+                },
+                Move {
+                    id: 7,
+                    from: 32,
+                    to: 8,
+                    len: 3
+                }
+            ]
+        );
+        assert_eq!(compaction.stuck, vec![8, 6, 5, 3]);
+        assert_eq!(compaction.disk.gaps().len(), 5);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(INPUT), 1928);
@@ -126,5 +374,7 @@ mod tests {
     #[test]
     fn test_part2() {
         assert_eq!(part2(INPUT), 2858);
+        // The empty file leaves a free span of 18 blocks
+        assert_eq!(part2("19091"), 2);
     }
 }
@@ -0,0 +1,390 @@
+//! Writes rendered frames as PPM or PNG image sequences or as an animated GIF
+
+use std::io;
+
+use crossterm::style::Color;
+
+use crate::parse::{self, ParseError};
+use crate::render::{Cell, Frame};
+
+type Rgb = [u8; 3];
+
+/// Maps cell colours to pixels. Cells without a colour are drawn in the foreground colour,
+/// except spaces and dots which are left as background.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub background: Rgb,
+    pub foreground: Rgb,
+    /// Replaces the default for a colour
+    pub overrides: Vec<(Color, Rgb)>,
+}
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: [16, 16, 24],
+            foreground: [200, 200, 200],
+            overrides: vec![],
+        }
+    }
+}
+impl Palette {
+    fn rgb(&self, cell: &Cell) -> Rgb {
+        let Some(color) = cell.color else {
+            return match cell.ch {
+                ' ' | '.' => self.background,
+                _ => self.foreground,
+            };
+        };
+        if let Some((_, rgb)) = self.overrides.iter().find(|(c, _)| *c == color) {
+            return *rgb;
+        }
+        match color {
+            Color::Black => [0, 0, 0],
+            Color::DarkGrey => [96, 96, 96],
+            Color::Red => [255, 85, 85],
+            Color::DarkRed => [170, 0, 0],
+            Color::Green => [85, 255, 85],
+            Color::DarkGreen => [0, 170, 0],
+            Color::Yellow => [255, 255, 85],
+            Color::DarkYellow => [170, 170, 0],
+            Color::Blue => [85, 85, 255],
+            Color::DarkBlue => [0, 0, 170],
+            Color::Magenta => [255, 85, 255],
+            Color::DarkMagenta => [170, 0, 170],
+            Color::Cyan => [85, 255, 255],
+            Color::DarkCyan => [0, 170, 170],
+            Color::White => [255, 255, 255],
+            Color::Grey => [170, 170, 170],
+            Color::Rgb { r, g, b } => [r, g, b],
+            Color::Reset | Color::AnsiValue(_) => self.foreground,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// Where to write, the extension picks the format. Image sequences get the frame number
+    /// added to the name, e.g. `day14_0001.png`.
+    pub out: String,
+    /// Width and height of a cell in pixels
+    pub cell: usize,
+    /// Time between GIF frames in hundredths of a second
+    pub delay: u16,
+    /// Frames dropped from the start
+    pub skip: usize,
+    /// Only every n:th frame is kept, along with the last one
+    pub every: usize,
+    /// Stops after this many frames
+    pub limit: Option<usize>,
+    pub palette: Palette,
+}
+impl Options {
+    pub fn new(out: &str) -> Self {
+        Self {
+            out: out.to_string(),
+            cell: 4,
+            delay: 5,
+            skip: 0,
+            every: 1,
+            limit: None,
+            palette: Palette::default(),
+        }
+    }
+
+    /// Reads options such as `out=day6.gif cell=8 delay=10 every=2 bg=ffffff red=ff0000` on top
+    /// of `defaults`. Any colour name crossterm knows can be given a hex value.
+    pub fn parse(args: &[String], defaults: Options) -> Result<Options, ParseError> {
+        let mut options = defaults;
+        for arg in args {
+            for (key, value) in parse::key_values(arg, 1)? {
+                let number = |value: &str| -> Result<usize, ParseError> {
+                    value
+                        .parse()
+                        .map_err(|_| ParseError::new(1, format!("invalid {key} '{value}'")))
+                };
+                match key {
+                    "out" => options.out = value.to_string(),
+                    "cell" => options.cell = number(value)?.max(1),
+                    "delay" => options.delay = number(value)?.min(u16::MAX as usize) as u16,
+                    "skip" => options.skip = number(value)?,
+                    "every" => options.every = number(value)?.max(1),
+                    "limit" => options.limit = Some(number(value)?),
+                    "bg" => options.palette.background = parse_hex(value)?,
+                    "fg" => options.palette.foreground = parse_hex(value)?,
+                    _ => match Color::try_from(key) {
+                        Ok(color) => options.palette.overrides.push((color, parse_hex(value)?)),
+                        Err(_) => {
+                            return Err(ParseError::new(1, format!("unknown option '{key}'")))
+                        }
+                    },
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn parse_hex(s: &str) -> Result<Rgb, ParseError> {
+    let s = s.trim_start_matches('#');
+    let value = u32::from_str_radix(s, 16)
+        .ok()
+        .filter(|_| s.len() == 6)
+        .ok_or_else(|| ParseError::new(1, format!("invalid colour '{s}', expected rrggbb")))?;
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+fn rasterize(frame: &Frame, cell: usize, palette: &Palette) -> Image {
+    let width = frame.cols * cell;
+    let mut pixels = Vec::with_capacity(width * frame.rows * cell);
+    for line in frame.lines() {
+        let row = line
+            .iter()
+            .flat_map(|c| std::iter::repeat_n(palette.rgb(c), cell))
+            .collect::<Vec<_>>();
+        for _ in 0..cell {
+            pixels.extend_from_slice(&row);
+        }
+    }
+    Image {
+        width,
+        height: frame.rows * cell,
+        pixels,
+    }
+}
+
+/// Every n:th item and the last one, which is usually where a simulation ends up
+fn sample<T>(items: impl Iterator<Item = T>, every: usize) -> impl Iterator<Item = T> {
+    let mut items = items.enumerate().peekable();
+    std::iter::from_fn(move || {
+        while let Some((i, item)) = items.next() {
+            if i % every == 0 || items.peek().is_none() {
+                return Some(item);
+            }
+        }
+        None
+    })
+}
+
+/// Writes the frames one at a time as they are rendered and returns how many were written
+pub fn export(frames: impl Iterator<Item = Frame>, options: &Options) -> io::Result<usize> {
+    let images = sample(frames.skip(options.skip), options.every)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .map(|f| rasterize(&f, options.cell, &options.palette));
+    let (stem, extension) = options.out.rsplit_once('.').unwrap_or((&options.out, ""));
+    let mut count = 0;
+    match extension {
+        "gif" => {
+            let file = io::BufWriter::new(std::fs::File::create(&options.out)?);
+            count = write_gif(file, images, options.delay)?;
+        }
+        "png" | "ppm" => {
+            for image in images {
+                let bytes = match extension {
+                    "png" => encode_png(&image)?,
+                    _ => encode_ppm(&image),
+                };
+                std::fs::write(format!("{stem}_{count:04}.{extension}"), bytes)?;
+                count += 1;
+            }
+        }
+        _ => {
+            return Err(invalid_input(format!(
+                "unknown image format '{}'",
+                options.out
+            )))
+        }
+    }
+    Ok(count)
+}
+
+/// Reads the options from the command line and exports, reporting how it went
+pub fn run(args: &[String], defaults: Options, frames: impl Iterator<Item = Frame>) {
+    let options = match Options::parse(args, defaults) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("Invalid option: {}", e.message);
+            return;
+        }
+    };
+    match export(frames, &options) {
+        Ok(count) => println!("Wrote {count} frames to {}", options.out),
+        Err(e) => println!("Export failed: {e}"),
+    }
+}
+
+fn encode_ppm(image: &Image) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+    out.extend(image.pixels.iter().flatten());
+    out
+}
+
+fn encode_png(image: &Image) -> io::Result<Vec<u8>> {
+    let too_large = || {
+        invalid_input(format!(
+            "{}x{} is too large for a PNG",
+            image.width, image.height
+        ))
+    };
+    let width = u32::try_from(image.width).map_err(|_| too_large())?;
+    let height = u32::try_from(image.height).map_err(|_| too_large())?;
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(image.pixels.as_flattened())
+        .map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+    Ok(out)
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// GIF sizes are 16 bits
+fn gif_size(image: &Image) -> io::Result<(u16, u16)> {
+    match (u16::try_from(image.width), u16::try_from(image.height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(invalid_input(format!(
+            "{}x{} is too large for a GIF, which allows at most {}x{}",
+            image.width,
+            image.height,
+            u16::MAX,
+            u16::MAX
+        ))),
+    }
+}
+
+/// Writes an animated GIF that loops forever, sized after the first image. Frames with more than
+/// 256 colours are quantized. Returns how many frames were written.
+fn write_gif<W: io::Write>(
+    out: W,
+    images: impl Iterator<Item = Image>,
+    delay: u16,
+) -> io::Result<usize> {
+    let mut images = images.peekable();
+    let (width, height) = match images.peek() {
+        Some(image) => gif_size(image)?,
+        None => (0, 0),
+    };
+    let mut encoder = gif::Encoder::new(out, width, height, &[]).map_err(io::Error::other)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(io::Error::other)?;
+    let mut count = 0;
+    for image in images {
+        let (width, height) = gif_size(&image)?;
+        let mut frame = gif::Frame::from_rgb_speed(width, height, image.pixels.as_flattened(), 10);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+        count += 1;
+    }
+    // Writes the trailer
+    encoder.into_inner()?.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::IVec2;
+
+    use crate::render::Highlight;
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(sample(0..10, 3).collect::<Vec<_>>(), [0, 3, 6, 9]);
+        assert_eq!(sample(0..11, 3).collect::<Vec<_>>(), [0, 3, 6, 9, 10]);
+        assert_eq!(sample(0..3, 1).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(sample(0..0, 2).count(), 0);
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let robot = [IVec2::new(1, 0)];
+        let frame = Frame::new(2, 1, '.').layer(&Highlight::new(&robot, '@').color(Color::Red));
+        let mut palette = Palette::default();
+        palette.overrides.push((Color::Red, [1, 2, 3]));
+        let image = rasterize(&frame, 2, &palette);
+        let bg = palette.background;
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(image.pixels, [bg, bg, [1, 2, 3], [1, 2, 3]].repeat(2));
+        assert_eq!(encode_ppm(&image)[..11], *b"P6\n4 2\n255\n");
+    }
+
+    #[test]
+    fn test_png() {
+        let image = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![[255, 0, 0], [0, 0, 255]],
+        };
+        let png = encode_png(&image).unwrap();
+        let mut reader = png::Decoder::new(&png[..]).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(buf[..info.buffer_size()], [255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_gif() {
+        let robot = [IVec2::new(1, 0)];
+        let frames = [
+            Frame::new(2, 1, '.'),
+            Frame::new(2, 1, '.').layer(&Highlight::new(&robot, '@')),
+        ];
+        let palette = Palette::default();
+        let images = frames.iter().map(|f| rasterize(f, 1, &palette));
+        let mut out = Vec::new();
+        assert_eq!(write_gif(&mut out, images, 10).unwrap(), 2);
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(&out[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (2, 1));
+        let mut decoded = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 10);
+            let pixels = frame.buffer.chunks(4).map(|p| [p[0], p[1], p[2]]);
+            decoded.push(pixels.collect::<Vec<_>>());
+        }
+        let (bg, fg) = (palette.background, palette.foreground);
+        assert_eq!(decoded, [vec![bg, bg], vec![bg, fg]]);
+    }
+
+    #[test]
+    fn test_gif_too_large() {
+        let image = Image {
+            width: u16::MAX as usize + 1,
+            height: 1,
+            pixels: vec![[0, 0, 0]; u16::MAX as usize + 1],
+        };
+        let error = write_gif(Vec::new(), [image].into_iter(), 10).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_options() {
+        let args = [
+            "cell=8 every=2".to_string(),
+            "red=ff0000 bg=#ffffff".to_string(),
+        ];
+        let options = Options::parse(&args, Options::new("day6.gif")).unwrap();
+        assert_eq!(options.out, "day6.gif");
+        assert_eq!((options.cell, options.every, options.delay), (8, 2, 5));
+        assert_eq!(options.palette.background, [255, 255, 255]);
+        assert_eq!(options.palette.overrides, vec![(Color::Red, [255, 0, 0])]);
+        assert!(Options::parse(&["bg=fff".to_string()], Options::new("x.gif")).is_err());
+        assert!(Options::parse(&["size=2".to_string()], Options::new("x.gif")).is_err());
+    }
+}
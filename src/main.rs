@@ -19,6 +19,9 @@ mod day14;
 mod day15;
 mod day16;
 mod day17;
+mod export;
+mod parse;
+mod render;
 
 fn main() {
     let entry_points = [
@@ -0,0 +1,206 @@
+//! Helpers for the input formats that show up in more than one day
+
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number
+    pub line: usize,
+    pub message: String,
+}
+impl ParseError {
+    pub fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn parse_value<T: FromStr>(s: &str, line: usize) -> Result<T, ParseError> {
+    s.parse::<T>()
+        .map_err(|_| ParseError::new(line, format!("invalid value '{s}'")))
+}
+
+/// Every integer in the line, ignoring anything in between. A `-` directly in front of a digit
+/// makes the number negative, so `1-2` gives 1 and -2. A number that does not fit in `T` is an
+/// error rather than skipped.
+pub fn ints<T: FromStr>(s: &str, line: usize) -> Result<Vec<T>, ParseError> {
+    let mut output = Vec::new();
+    let mut start = None;
+    for (i, c) in s.char_indices().chain([(s.len(), ' ')]) {
+        let digit = c.is_ascii_digit();
+        let sign = c == '-' && s[i + 1..].starts_with(|c: char| c.is_ascii_digit());
+        match start {
+            Some(_) if digit => {}
+            Some(begin) => {
+                output.push(parse_value(&s[begin..i], line)?);
+                start = (digit || sign).then_some(i);
+            }
+            None => start = (digit || sign).then_some(i),
+        }
+    }
+    Ok(output)
+}
+
+/// Whitespace separated values where every field has to parse
+pub fn fields<T: FromStr>(s: &str, line: usize) -> Result<Vec<T>, ParseError> {
+    s.split_whitespace().map(|f| parse_value(f, line)).collect()
+}
+
+/// Reads `n` whitespace separated columns, skipping blank lines
+pub fn columns<T: FromStr>(input: &str, n: usize) -> Result<Vec<Vec<T>>, ParseError> {
+    let mut columns = (0..n).map(|_| Vec::new()).collect::<Vec<_>>();
+    for (i, l) in input.lines().enumerate() {
+        if l.trim().is_empty() {
+            continue;
+        }
+        let values = fields::<T>(l, i + 1)?;
+        if values.len() != n {
+            return Err(ParseError::new(
+                i + 1,
+                format!("expected {n} columns, found {}", values.len()),
+            ));
+        }
+        for (column, v) in columns.iter_mut().zip(values) {
+            column.push(v);
+        }
+    }
+    Ok(columns)
+}
+
+/// Lines between blank lines
+#[derive(Debug, PartialEq, Eq)]
+pub struct Section<'a> {
+    /// Line number of the first line
+    pub line: usize,
+    pub lines: Vec<&'a str>,
+}
+
+/// Splits the input on blank lines. Any number of blank lines may separate two sections.
+pub fn sections(input: &str) -> Vec<Section<'_>> {
+    let mut sections = Vec::new();
+    let mut current: Option<Section> = None;
+    for (i, l) in input.lines().enumerate() {
+        if l.trim().is_empty() {
+            sections.extend(current.take());
+        } else {
+            current
+                .get_or_insert_with(|| Section {
+                    line: i + 1,
+                    lines: vec![],
+                })
+                .lines
+                .push(l);
+        }
+    }
+    sections.extend(current);
+    sections
+}
+
+/// Splits `p=0,4 v=3,-3` into `[("p", "0,4"), ("v", "3,-3")]`
+pub fn key_values(s: &str, line: usize) -> Result<Vec<(&str, &str)>, ParseError> {
+    s.split_whitespace()
+        .map(|kv| {
+            kv.split_once('=')
+                .ok_or_else(|| ParseError::new(line, format!("expected key=value, found '{kv}'")))
+        })
+        .collect()
+}
+
+/// Looks up the value for `key`
+pub fn value<'a>(pairs: &[(&str, &'a str)], key: &str, line: usize) -> Result<&'a str, ParseError> {
+    pairs
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| *v)
+        .ok_or_else(|| ParseError::new(line, format!("missing key '{key}'")))
+}
+
+/// Parses two values separated by `sep`, such as `3,-3`
+pub fn pair<T: FromStr>(s: &str, sep: char, line: usize) -> Result<(T, T), ParseError> {
+    let (lhs, rhs) = s
+        .split_once(sep)
+        .ok_or_else(|| ParseError::new(line, format!("expected two values in '{s}'")))?;
+    Ok((
+        parse_value(lhs.trim(), line)?,
+        parse_value(rhs.trim(), line)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ints() {
+        assert_eq!(ints::<i64>("p=0,4 v=3,-3", 1), Ok(vec![0, 4, 3, -3]));
+        assert_eq!(ints::<i32>("Button A: X+94, Y-34", 1), Ok(vec![94, -34]));
+        assert_eq!(ints::<i32>("1-2 - 3-", 1), Ok(vec![1, -2, 3]));
+        assert_eq!(ints::<i32>("", 1), Ok(vec![]));
+        assert_eq!(
+            ints::<u8>("7 -1 300", 4).unwrap_err().to_string(),
+            "line 4: invalid value '-1'"
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        assert_eq!(
+            columns::<i32>("3   4\n4   3\n\n2 5\n", 2),
+            Ok(vec![vec![3, 4, 2], vec![4, 3, 5]])
+        );
+        assert_eq!(
+            columns::<i32>("3 4\n4 3 1", 2),
+            Err(ParseError::new(2, "expected 2 columns, found 3"))
+        );
+        assert_eq!(
+            columns::<i32>("3 4\nx 3", 2).unwrap_err().to_string(),
+            "line 2: invalid value 'x'"
+        );
+    }
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(fields::<i64>(" 10 19", 1), Ok(vec![10, 19]));
+        assert!(fields::<i64>("15, 6", 1).is_err());
+    }
+
+    #[test]
+    fn test_sections() {
+        let input = "a\r\nb\r\n\r\n\r\nc\n\n";
+        assert_eq!(
+            sections(input),
+            vec![
+                Section {
+                    line: 1,
+                    lines: vec!["a", "b"]
+                },
+                Section {
+                    line: 5,
+                    lines: vec!["c"]
+                }
+            ]
+        );
+        assert!(sections("\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_key_values() {
+        let kv = key_values("p=0,4 v=3,-3", 1).unwrap();
+        assert_eq!(kv, vec![("p", "0,4"), ("v", "3,-3")]);
+        assert_eq!(pair::<i32>(kv[1].1, ',', 1), Ok((3, -3)));
+        assert_eq!(value(&kv, "p", 1), Ok("0,4"));
+        assert_eq!(
+            value(&kv, "x", 3),
+            Err(ParseError::new(3, "missing key 'x'"))
+        );
+        assert!(key_values("p=1 v", 1).is_err());
+        assert!(pair::<i32>("1;2", ',', 1).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Character frames shared by the grid visualisations. A base map and any overlays draw onto a
+//! `Frame`, which can then be shown as plain text, with ANSI colours or as a ratatui widget.
+
+use crossterm::style::{Color, Stylize};
+use glam::IVec2;
+use ratatui::{
+    style::Style,
+    text::{Line, Span, Text},
+    widgets::Paragraph,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub color: Option<Color>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub cols: usize,
+    pub rows: usize,
+    cells: Vec<Cell>,
+}
+
+/// Anything that can be drawn onto a frame, either as the base map or as an overlay
+pub trait Render {
+    /// Draws on top of whatever is already in the frame
+    fn render(&self, frame: &mut Frame);
+}
+
+impl Frame {
+    pub fn new(cols: usize, rows: usize, fill: char) -> Self {
+        Self {
+            cols,
+            rows,
+            cells: vec![
+                Cell {
+                    ch: fill,
+                    color: None
+                };
+                cols * rows
+            ],
+        }
+    }
+
+    fn pos_to_idx(&self, pos: IVec2) -> Option<usize> {
+        if pos.x >= self.cols as i32 || pos.y >= self.rows as i32 || pos.x < 0 || pos.y < 0 {
+            return None;
+        }
+        Some((pos.y * (self.cols as i32) + pos.x) as usize)
+    }
+
+    /// Positions outside the frame are ignored
+    pub fn set(&mut self, pos: IVec2, ch: char, color: Option<Color>) {
+        if let Some(idx) = self.pos_to_idx(pos) {
+            self.cells[idx] = Cell { ch, color };
+        }
+    }
+
+    /// Adds a layer on top of the frame
+    pub fn layer(mut self, layer: &impl Render) -> Self {
+        layer.render(&mut self);
+        self
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &[Cell]> {
+        self.cells.chunks(self.cols.max(1))
+    }
+
+    /// Consecutive cells of the same colour in a line
+    fn runs(line: &[Cell]) -> impl Iterator<Item = (String, Option<Color>)> + '_ {
+        line.chunk_by(|a, b| a.color == b.color)
+            .map(|run| (run.iter().map(|c| c.ch).collect(), run[0].color))
+    }
+
+    pub fn to_text(&self) -> String {
+        self.lines()
+            .map(|line| line.iter().map(|c| c.ch).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn to_ansi(&self) -> String {
+        self.lines()
+            .map(|line| {
+                Self::runs(line)
+                    .map(|(s, color)| match color {
+                        Some(color) => s.with(color).to_string(),
+                        None => s,
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn to_widget(&self) -> Paragraph<'static> {
+        let lines = self
+            .lines()
+            .map(|line| {
+                Line::from(
+                    Self::runs(line)
+                        .map(|(s, color)| match color {
+                            Some(color) => Span::styled(s, Style::default().fg(color.into())),
+                            None => Span::raw(s),
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        Paragraph::new(Text::from(lines))
+    }
+}
+
+/// Marks a set of positions, e.g. a path or the robots of a simulation
+pub struct Highlight<'a> {
+    positions: &'a [IVec2],
+    ch: char,
+    color: Option<Color>,
+}
+impl<'a> Highlight<'a> {
+    pub fn new(positions: &'a [IVec2], ch: char) -> Self {
+        Self {
+            positions,
+            ch,
+            color: None,
+        }
+    }
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+}
+impl Render for Highlight<'_> {
+    fn render(&self, frame: &mut Frame) {
+        for pos in self.positions {
+            frame.set(*pos, self.ch, self.color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers() {
+        let path = [IVec2::new(0, 0), IVec2::new(1, 0), IVec2::new(5, 5)];
+        let robot = [IVec2::new(1, 0)];
+        let frame = Frame::new(3, 2, '.')
+            .layer(&Highlight::new(&path, 'O'))
+            .layer(&Highlight::new(&robot, '@').color(Color::Red));
+        assert_eq!(frame.to_text(), "O@.\n...");
+        assert_eq!(frame.to_ansi(), format!("O{}.\n...", "@".with(Color::Red)));
+    }
+
+    #[test]
+    fn test_widget() {
+        let robot = [IVec2::new(1, 0)];
+        let frame = Frame::new(3, 1, '.').layer(&Highlight::new(&robot, '@').color(Color::Red));
+        let widget = frame.to_widget();
+        let expected = Paragraph::new(Text::from(vec![Line::from(vec![
+            Span::raw("."),
+            Span::styled("@", Style::default().fg(ratatui::style::Color::LightRed)),
+            Span::raw("."),
+        ])]));
+        assert_eq!(widget, expected);
+    }
+}